@@ -0,0 +1,92 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::net;
+use crate::provider::{Emote, EmoteProvider, Page, SearchQuery, Variant, read_body};
+use futures::{FutureExt, future::BoxFuture};
+use gpui::http_client::Url;
+use serde::Deserialize;
+use serde_json;
+
+pub const PROVIDER_ID: &str = "bttv";
+
+// BTTV api refuses to search shared emotes with shorter queries.
+const MIN_QUERY_LEN: usize = 3;
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    id: String,
+    code: String,
+    animated: bool,
+}
+
+pub struct Bttv;
+
+impl EmoteProvider for Bttv {
+    fn id(&self) -> &'static str {
+        PROVIDER_ID
+    }
+
+    fn search(&self, query: SearchQuery) -> BoxFuture<'static, Result<Page>> {
+        query_bttv(query).boxed()
+    }
+
+    fn can_search(&self, query: &SearchQuery) -> bool {
+        query.tags.is_empty() && query.text.chars().count() >= MIN_QUERY_LEN
+    }
+
+    fn image_url(&self, id: &str) -> String {
+        // @NOTE: bttv serves every emote (animated ones too) as webp if asked explicitly.
+        format!("https://cdn.betterttv.net/emote/{}/3x.webp", id)
+    }
+}
+
+// @NOTE: bttv search has no sort options, results are always ordered by popularity.
+pub async fn query_bttv(query: SearchQuery) -> Result<Page> {
+    let per_page = config::get().search.per_page;
+    let offset = ((query.page - 1) * per_page).to_string();
+    let limit = per_page.to_string();
+    let url = Url::parse_with_params(
+        "https://api.betterttv.net/3/emotes/shared/search",
+        &[
+            ("query", query.text.as_str()),
+            ("offset", offset.as_str()),
+            ("limit", limit.as_str()),
+        ],
+    )
+    .expect("rip bttv url");
+
+    let response = net::CLIENT.get(url.as_str()).await?;
+    let raw_response = read_body(PROVIDER_ID, &response)?;
+
+    let items = serde_json::from_str::<Vec<Item>>(&raw_response).map_err(|e| Error::Api(PROVIDER_ID, e.to_string()))?;
+
+    // bttv doesn't tell the total count, so full page is the only hint that there might be more
+    let has_more = items.len() == per_page;
+    let emotes = items
+        .into_iter()
+        .map(|emote| Emote {
+            provider: PROVIDER_ID.to_string(),
+            url: Bttv.image_url(&emote.id),
+            variants: variants(&emote.id),
+            id: emote.id,
+            name: emote.code,
+            animated: emote.animated,
+            zero_width: false, // bttv doesn't mark those in the api
+        })
+        .collect();
+
+    Ok(Page { emotes, has_more })
+}
+
+// Every emote has the same three scales, bttv doesn't tell the sizes in the search results.
+fn variants(id: &str) -> Vec<Variant> {
+    (1..=3)
+        .map(|scale| Variant {
+            url: format!("https://cdn.betterttv.net/emote/{}/{}x.webp", id, scale),
+            mime: "image/webp".to_string(),
+            scale,
+            width: None,
+            frame_count: None,
+        })
+        .collect()
+}
@@ -1,21 +1,51 @@
-use crate::{CACHE_DIR, seventv::WebmEmote};
-use futures::AsyncReadExt as _;
+use crate::error::{self, Error};
+use crate::{CACHE_DIR, config, disk_cache, net, provider::Emote};
 use futures::FutureExt;
+use futures::channel::oneshot;
 use gpui::{
     App, AppContext, Asset, AssetLogger, Entity, ImageAssetLoader, ImageCache, ImageCacheError, ImageCacheItem,
-    RenderImage, Resource, Window, hash, http_client::AsyncBody, http_client::HttpClient,
+    RenderImage, Resource, Window, hash,
 };
-use reqwest_client::ReqwestClient;
-use std::fs::{self, File};
-use std::io::prelude::*;
+use std::fs;
 use std::path::{Path, PathBuf};
-use std::{collections::HashMap, sync::Arc};
+use std::time::Instant;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 // Cache implementation, based on the default gpui cache, but with reads/writes to disk as an
 // intermediate step between in-memory cache and loading from remote source.
 pub struct HashMapImageCache {
-    data: HashMap<u64, ImageCacheItem>,
-    client: Arc<ReqwestClient>,
+    data: HashMap<u64, CacheEntry>,
+    // decoded size of every loaded image, evicting starts once it goes over the memory budget
+    used_bytes: usize,
+    // Urls (or paths) of the images on screen, or close to it. gpui only redraws when something
+    // changes, so the last load of an image says nothing about whether it's still on screen.
+    visible: HashSet<String>,
+}
+
+struct CacheEntry {
+    item: ImageCacheItem,
+    // url or path the image was loaded from
+    source: String,
+    last_used: Instant,
+    // zero until the image is loaded
+    bytes: usize,
+    // only for images downloaded from remote sources, dropping it cancels the download
+    cancel: Option<oneshot::Sender<()>>,
+}
+
+impl CacheEntry {
+    fn new(item: ImageCacheItem, source: String) -> Self {
+        Self {
+            item,
+            source,
+            last_used: Instant::now(),
+            bytes: 0,
+            cancel: None,
+        }
+    }
 }
 
 impl HashMapImageCache {
@@ -24,11 +54,12 @@ impl HashMapImageCache {
     pub fn new(cx: &mut App) -> Entity<Self> {
         let e = cx.new(|_cx| HashMapImageCache {
             data: HashMap::new(),
-            client: Arc::new(ReqwestClient::new()),
+            used_bytes: 0,
+            visible: HashSet::new(),
         });
         cx.observe_release(&e, |image_cache, cx| {
-            for (_, mut item) in std::mem::replace(&mut image_cache.data, HashMap::new()) {
-                if let Some(Ok(image)) = item.get() {
+            for (_, mut entry) in std::mem::replace(&mut image_cache.data, HashMap::new()) {
+                if let Some(Ok(image)) = entry.item.get() {
                     cx.drop_image(image, None);
                 }
             }
@@ -48,45 +79,52 @@ impl HashMapImageCache {
     ) -> Option<Result<Arc<RenderImage>, ImageCacheError>> {
         let hash = hash(source);
 
-        if let Some(item) = self.data.get_mut(&hash) {
-            return item.get(); // will return None if still loading, I think - andrew
+        if let Some(entry) = self.data.get_mut(&hash) {
+            entry.last_used = Instant::now();
+            let result = entry.item.get(); // will return None if still loading, I think - andrew
+
+            // first time seeing the loaded image, only now we know how much memory it takes
+            if entry.bytes == 0
+                && let Some(Ok(image)) = &result
+            {
+                entry.bytes = image_bytes(image);
+                self.used_bytes += entry.bytes;
+                let memory_budget = config::get().cache.memory_budget();
+                if self.used_bytes > memory_budget {
+                    self.evict(memory_budget, window, cx);
+                }
+            }
+            return result;
         }
 
         match source {
             Resource::Uri(uri) => {
                 let source_url = format!("{}", uri);
-                let new_source_path = WebmEmote::path(&source_url);
-                let new_source = Resource::from(PathBuf::from(new_source_path.clone()));
+                let new_source_path = Emote::path(&source_url);
+                let new_source = Resource::from(PathBuf::from(new_source_path));
                 let fut = AssetLogger::<ImageAssetLoader>::load(new_source, cx);
+                let (cancel, cancelled) = oneshot::channel::<()>();
+                let source = source_url.clone();
 
-                let client = self.client.clone();
-                let task;
-                if Path::new(&new_source_path).exists() {
-                    task = cx.background_executor().spawn(fut).shared();
-                } else {
-                    task = cx
-                        .background_executor()
-                        .spawn(async move {
-                            fs::create_dir_all(format!("{}/webm", *CACHE_DIR)).expect("rip webm dir");
-
-                            let mut file = File::create(new_source_path).expect("rip webp file");
-                            let mut raw_response = Vec::new();
-                            client
-                                .get(&source_url, AsyncBody::empty(), true)
-                                .await
-                                .expect("rip download request")
-                                .into_body()
-                                .read_to_end(&mut raw_response)
-                                .await
-                                .expect(&format!("rip download body: {}", &source_url));
-                            file.write_all(&raw_response).expect("rip write file");
-
-                            fut.await
-                        })
-                        .shared();
-                }
+                // Cached images are verified before loading as well, so the corrupt ones (e.g.
+                // from older versions writing straight into the destination) get downloaded again.
+                let task = cx
+                    .background_executor()
+                    .spawn(async move {
+                        let cancelled = async {
+                            _ = cancelled.await;
+                            Err(Error::Network(format!("cancelled download of {}", source_url)))
+                        };
+                        if let Err(err) = smol::future::or(download(&source_url), cancelled).await {
+                            return Err(ImageCacheError::Asset(err.to_string().into()));
+                        }
+                        fut.await
+                    })
+                    .shared();
 
-                self.data.insert(hash, ImageCacheItem::Loading(task.clone()));
+                let mut entry = CacheEntry::new(ImageCacheItem::Loading(task.clone()), source);
+                entry.cancel = Some(cancel);
+                self.data.insert(hash, entry);
 
                 let entity = window.current_view();
                 window
@@ -104,7 +142,12 @@ impl HashMapImageCache {
             _ => {
                 let fut = AssetLogger::<ImageAssetLoader>::load(source.clone(), cx);
                 let task = cx.background_executor().spawn(fut).shared();
-                self.data.insert(hash, ImageCacheItem::Loading(task.clone()));
+                let source = match source {
+                    Resource::Path(path) => path.display().to_string(),
+                    _ => String::new(),
+                };
+                self.data
+                    .insert(hash, CacheEntry::new(ImageCacheItem::Loading(task.clone()), source));
 
                 let entity = window.current_view();
                 window
@@ -123,8 +166,101 @@ impl HashMapImageCache {
         None
     }
 
-    // @TODO: Should probably be unloading assets from memory at some point..
-    //  - https://github.com/zed-industries/zed/blob/053fafa90ead15ede22aee67f1f5ed4aa8e48819/crates/gpui/src/elements/image_cache.rs#L280-L297
+    /// The image if it's done loading, without starting a download or counting as a use.
+    pub fn loaded(&mut self, source: &Resource) -> Option<Arc<RenderImage>> {
+        self.data.get_mut(&hash(source))?.item.get()?.ok()
+    }
+
+    /// Sets the urls (or paths) of the images in the grid. Downloads of images that are not there
+    /// anymore are cancelled, e.g. thumbnails of a search that was replaced before they were
+    /// loaded. Those are loaded again if they show up later.
+    pub fn set_shown(&mut self, shown: HashSet<String>) {
+        self.data
+            .retain(|_, entry| entry.cancel.is_none() || shown.contains(&entry.source) || entry.item.get().is_some());
+    }
+
+    /// Sets the urls (or paths) of the images on screen, only the other ones are evicted.
+    pub fn set_visible(&mut self, visible: HashSet<String>) {
+        self.visible = visible;
+    }
+
+    // Drops the least recently used images until we are back under the memory budget. Images on
+    // screen are kept even if that means going over it, those would be loaded again as soon as
+    // they are drawn.
+    fn evict(&mut self, memory_budget: usize, window: &mut Window, cx: &mut App) {
+        let mut candidates: Vec<(u64, Instant)> = self
+            .data
+            .iter()
+            .filter(|(_, entry)| entry.bytes > 0 && !self.visible.contains(&entry.source))
+            .map(|(hash, entry)| (*hash, entry.last_used))
+            .collect();
+        candidates.sort_by_key(|(_, last_used)| *last_used);
+
+        for (hash, _) in candidates {
+            if self.used_bytes <= memory_budget {
+                break;
+            }
+            let Some(mut entry) = self.data.remove(&hash) else {
+                continue;
+            };
+            self.used_bytes -= entry.bytes;
+            if let Some(Ok(image)) = entry.item.get() {
+                cx.drop_image(image, Some(window));
+            }
+        }
+        println!("IMAGE CACHE AFTER EVICTION: {} MB", self.used_bytes / 1024 / 1024);
+    }
+}
+
+// Every frame of the image is kept decoded in memory.
+fn image_bytes(image: &RenderImage) -> usize {
+    (0..image.frame_count())
+        .filter_map(|frame_index| image.as_bytes(frame_index))
+        .map(|bytes| bytes.len())
+        .sum()
+}
+
+/// Download the image into the disk cache, unless it's already there. Returns the path on disk.
+pub async fn download(url: &str) -> error::Result<String> {
+    let path = Emote::path(url);
+    if Path::new(&path).exists() {
+        // local emotes are not ours to verify, those are just read as they are
+        if !path.starts_with(&*CACHE_DIR) || fs::read(&path).is_ok_and(|bytes| disk_cache::is_complete_image(&bytes)) {
+            disk_cache::touch(&path);
+            return Ok(path);
+        }
+        println!("RE-DOWNLOADING CORRUPT CACHED IMAGE: {}", path);
+    }
+
+    let response = net::CLIENT.get(url).await?;
+    if !response.status.is_success() {
+        return Err(Error::Network(format!("status {} for {}", response.status, url)));
+    }
+    if !disk_cache::is_complete_image(&response.body) {
+        return Err(Error::Network(format!("incomplete image from {}", url)));
+    }
+    disk_cache::write_atomic(&path, &response.body)?;
+
+    Ok(path)
+}
+
+/// Makes sure that the full size image of the emote is in the disk cache. If it can't be
+/// downloaded (e.g. when offline), the emote falls back to its cached thumbnail instead.
+pub async fn fetch_full_size(emote: Emote) -> error::Result<Emote> {
+    match download(&emote.url).await {
+        Ok(_) => Ok(emote),
+        Err(err) => {
+            let thumbnail_url = emote.thumbnail_url().clone();
+            if !Path::new(&Emote::path(&thumbnail_url)).exists() {
+                return Err(err);
+            }
+            println!("FALLING BACK TO THUMBNAIL: {}", err);
+            Ok(Emote {
+                url: thumbnail_url,
+                ..emote
+            })
+        }
+    }
 }
 
 impl ImageCache for HashMapImageCache {
@@ -0,0 +1,267 @@
+use crate::error::Result;
+use crate::provider::Emote;
+use crate::transcode;
+use image::error::{ImageError, ParameterError, ParameterErrorKind};
+use image::imageops::{self, FilterType};
+use image::{AnimationDecoder, ImageFormat, ImageReader, Rgba, RgbaImage, codecs::webp::WebPDecoder};
+use serde::Deserialize;
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor, prelude::*};
+use std::path::PathBuf;
+use std::time::Duration;
+use wl_clipboard_rs::copy::{ClipboardType, MimeSource, MimeType, Options, Source};
+
+/// Size of the copied image. Scales set the height relative to the usual 32px chat emote (the
+/// 1x image of most providers, wide emotes are just wider), explicit sizes are the longest side
+/// in pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum OutputSize {
+    #[default]
+    Native,
+    Scale(u32),
+    Pixels(u32),
+}
+
+impl TryFrom<String> for OutputSize {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        Self::parse(&value)
+            .ok_or_else(|| format!("invalid output size {:?}, expected native, 1x to 4x or pixels", value))
+    }
+}
+
+impl OutputSize {
+    const BASE_SIZE: u32 = 32;
+
+    /// Parses `native`, a scale like `2x` or the size in pixels like `64`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim().to_lowercase();
+        if value == "native" {
+            return Some(Self::Native);
+        }
+        match value.strip_suffix('x') {
+            Some(scale) => scale
+                .parse()
+                .ok()
+                .filter(|scale| (1..=4).contains(scale))
+                .map(Self::Scale),
+            None => value.parse().ok().filter(|size| *size > 0).map(Self::Pixels),
+        }
+    }
+
+    /// Cycles through native and the scales, explicit sizes only come from the config.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Native => Self::Scale(1),
+            Self::Scale(scale) if scale < 4 => Self::Scale(scale + 1),
+            _ => Self::Native,
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            Self::Native => "native".to_string(),
+            Self::Scale(scale) => format!("{}x", scale),
+            Self::Pixels(size) => format!("{}px", size),
+        }
+    }
+
+    /// Dimensions of the output for an image of the given dimensions, keeping the aspect ratio.
+    /// None if the image is kept as it is.
+    pub fn dimensions(self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let ratio = match self {
+            Self::Native => return None,
+            Self::Scale(scale) => (scale * Self::BASE_SIZE) as f64 / height as f64,
+            Self::Pixels(size) => size as f64 / width.max(height) as f64,
+        };
+        let dimensions = (
+            ((width as f64 * ratio).round() as u32).max(1),
+            ((height as f64 * ratio).round() as u32).max(1),
+        );
+        (dimensions != (width, height)).then_some(dimensions)
+    }
+
+    /// Resamples the image to the output size.
+    pub fn resize(self, image: RgbaImage) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        match self.dimensions(width, height) {
+            Some((width, height)) => imageops::resize(&image, width, height, FilterType::Lanczos3),
+            None => image,
+        }
+    }
+}
+
+/// Copy the emote image from the disk cache into the clipboard. With `animated` set, animated
+/// emotes are copied as the original webp together with a gif for apps that can't play webp.
+pub fn copy_emote(emote: &Emote, animated: bool, size: OutputSize) -> Result<()> {
+    let source_path = Emote::path(&emote.url);
+
+    match animated_webp_decoder(&source_path)? {
+        Some(_) if animated => copy_animated(emote, &source_path, size),
+        Some(webp_decoder) => copy_webp_frame(emote, webp_decoder, Duration::ZERO, size),
+        None => {
+            let image = ImageReader::open(&source_path)?
+                .with_guessed_format()?
+                .decode()?
+                .to_rgba8();
+
+            if size == OutputSize::Native {
+                let mut buffer: Vec<u8> = Vec::new();
+                image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+                return copy(vec![
+                    ("image/webp", buffer),
+                    ("text/x-moz-url", format!("file://{}", &source_path).into_bytes()),
+                ]);
+            }
+            copy_static(emote, size.resize(image))
+        }
+    }
+}
+
+/// Copy the frame shown `elapsed` into the animation as a static sticker. Emotes that aren't
+/// animated are copied the same way as with `copy_emote`.
+pub fn copy_frame(emote: &Emote, elapsed: Duration, size: OutputSize) -> Result<()> {
+    match animated_webp_decoder(&Emote::path(&emote.url))? {
+        Some(webp_decoder) => copy_webp_frame(emote, webp_decoder, elapsed, size),
+        None => copy_emote(emote, false, size),
+    }
+}
+
+fn copy_webp_frame(
+    emote: &Emote,
+    mut webp_decoder: WebPDecoder<BufReader<File>>,
+    elapsed: Duration,
+    size: OutputSize,
+) -> Result<()> {
+    webp_decoder.set_background_color(Rgba([0, 0, 0, 0]))?;
+    let frames = webp_decoder.into_frames().collect_frames()?;
+
+    // Same as the gpui player, every frame is shown for its own delay and the animation loops.
+    let total: Duration = frames.iter().map(|frame| Duration::from(frame.delay())).sum();
+    let mut position = if total.is_zero() {
+        Duration::ZERO
+    } else {
+        Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64)
+    };
+    let frame = frames
+        .iter()
+        .find(|frame| {
+            let delay = Duration::from(frame.delay());
+            if position < delay {
+                return true;
+            }
+            position -= delay;
+            false
+        })
+        .or(frames.first())
+        .ok_or(ImageError::Parameter(ParameterError::from_kind(
+            ParameterErrorKind::NoMoreData,
+        )))?;
+
+    copy_static(emote, size.resize(frame.buffer().clone()))
+}
+
+fn copy_static(emote: &Emote, image: RgbaImage) -> Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+
+    // @TODO: We have to create a tmp fake file with the single frame for telegram to recognize
+    //  the format correctly. I'm not sure how hackable it is, we could try to fake it and lie
+    //  to telegram somehow, but this is probably fine for the future. In the future might want
+    //  to create this file in the persistent cache during download.
+    let final_path = format!("/tmp/{}.webp", emote.id.replace('/', "_")); // local ids are paths
+    File::create(final_path.clone())?.write_all(&buffer)?;
+
+    copy(vec![
+        ("image/webp", buffer),
+        ("text/x-moz-url", format!("file://{}", &final_path).into_bytes()),
+    ])
+}
+
+// @NOTE: the original webp can't be resized, there is no animated webp encoder in `image`. Only
+//  the gif follows the output size.
+fn copy_animated(emote: &Emote, source_path: &str, size: OutputSize) -> Result<()> {
+    let webp = fs::read(source_path)?;
+    let gif = fs::read(transcode::webp_to_gif(emote, size)?)?;
+
+    copy(vec![
+        ("image/webp", webp),
+        ("image/gif", gif),
+        ("text/x-moz-url", format!("file://{}", source_path).into_bytes()),
+    ])
+}
+
+fn copy(sources: Vec<(&str, Vec<u8>)>) -> Result<()> {
+    let mut opts = Options::new();
+    opts.omit_additional_text_mime_types(true); // do not add default mimetypes
+    opts.clipboard(ClipboardType::Both);
+    opts.copy_multi(
+        sources
+            .into_iter()
+            .map(|(mime, bytes)| MimeSource {
+                source: Source::Bytes(bytes.into()),
+                mime_type: MimeType::Specific(mime.to_string()),
+            })
+            .collect(),
+    )?;
+
+    Ok(())
+}
+
+// Only webp emotes are played as animations for now, everything else is copied as a static image.
+fn animated_webp_decoder(path: &str) -> Result<Option<WebPDecoder<BufReader<File>>>> {
+    let f = File::open(PathBuf::from(path))?;
+    let Ok(webp_decoder) = WebPDecoder::new(BufReader::new(f)) else {
+        return Ok(None);
+    };
+    Ok(webp_decoder.has_animation().then_some(webp_decoder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_output_size() {
+        assert_eq!(OutputSize::parse("native"), Some(OutputSize::Native));
+        assert_eq!(OutputSize::parse(" Native "), Some(OutputSize::Native));
+        assert_eq!(OutputSize::parse("2x"), Some(OutputSize::Scale(2)));
+        assert_eq!(OutputSize::parse("4X"), Some(OutputSize::Scale(4)));
+        assert_eq!(OutputSize::parse("64"), Some(OutputSize::Pixels(64)));
+    }
+
+    #[test]
+    fn parse_invalid_output_size() {
+        assert_eq!(OutputSize::parse("0x"), None);
+        assert_eq!(OutputSize::parse("5x"), None);
+        assert_eq!(OutputSize::parse("x"), None);
+        assert_eq!(OutputSize::parse("0"), None);
+        assert_eq!(OutputSize::parse("-1"), None);
+        assert_eq!(OutputSize::parse(""), None);
+    }
+
+    #[test]
+    fn scale_sets_the_height() {
+        assert_eq!(OutputSize::Scale(1).dimensions(128, 128), Some((32, 32)));
+        assert_eq!(OutputSize::Scale(2).dimensions(384, 128), Some((192, 64)));
+        assert_eq!(OutputSize::Scale(4).dimensions(128, 128), None);
+    }
+
+    #[test]
+    fn pixels_set_the_longest_side() {
+        assert_eq!(OutputSize::Pixels(64).dimensions(384, 128), Some((64, 21)));
+        assert_eq!(OutputSize::Pixels(64).dimensions(32, 128), Some((16, 64)));
+        assert_eq!(OutputSize::Pixels(1).dimensions(384, 4), Some((1, 1)));
+    }
+
+    #[test]
+    fn native_keeps_the_image() {
+        assert_eq!(OutputSize::Native.dimensions(384, 128), None);
+        assert_eq!(OutputSize::Scale(1).dimensions(0, 0), None);
+    }
+}
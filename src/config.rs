@@ -0,0 +1,320 @@
+use crate::clipboard::OutputSize;
+use crate::error::{Error, Result};
+use crate::provider::SearchSettings;
+use crate::{APP_NAME, xdg};
+use gpui::{Rgba, rgb};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+/// How often the config file is checked for changes.
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    static ref CONFIG: RwLock<Arc<Config>> = RwLock::new(Arc::new(Config::default()));
+}
+
+/// Everything that can be tuned per machine, read from `$XDG_CONFIG_HOME/kemote/config.toml`.
+/// Missing values keep their defaults, so an empty (or missing) file is a valid config.
+///
+/// The window size and the cache dir are used only at startup, everything else is applied as
+/// soon as the file is saved.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// How many of the recently copied emotes are remembered.
+    pub recent_emotes: usize,
+    /// Directories searched by the local provider, including their subdirectories (but not symlinked ones).
+    pub local_dirs: Vec<PathBuf>,
+    /// Size of the copied images, e.g. `native`, `2x` or `64`. It can still be changed from the
+    /// picker, this is only the initial one.
+    pub output_size: OutputSize,
+    pub window: WindowConfig,
+    pub colors: Colors,
+    pub search: SearchSettings,
+    pub seventv: SevenTvConfig,
+    pub cache: CacheConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            recent_emotes: 15,
+            local_dirs: vec![],
+            output_size: OutputSize::default(),
+            window: WindowConfig::default(),
+            colors: Colors::default(),
+            search: SearchSettings::default(),
+            seventv: SevenTvConfig::default(),
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        // width: 24 + ((80 + 2 * 2) * 10 + 24 * 9) + 24 = 1104 (2px focus ring around every emote)
+        Self {
+            width: 1104.,
+            height: 850.,
+        }
+    }
+}
+
+/// Colors of the picker, written as `#rrggbb`. Defaults are from the catppuccin frappe palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    pub background: Color,
+    /// Header and footer.
+    pub bar: Color,
+    pub input: Color,
+    pub text: Color,
+    pub error: Color,
+    pub error_text: Color,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Self {
+            background: Color(0x626880),
+            bar: Color(0x414559),
+            input: Color(0x838ba7),
+            text: Color(0xc6d0f5),
+            error: Color(0xe78284),
+            error_text: Color(0x303446),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Color(pub u32);
+
+impl Color {
+    pub fn rgb(self) -> Rgba {
+        rgb(self.0)
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(Color)
+            .ok_or_else(|| format!("invalid color {:?}, expected #rrggbb", value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SevenTvConfig {
+    /// GraphQL endpoint of the 7tv api.
+    pub api_url: String,
+}
+
+impl Default for SevenTvConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.7tv.app/v4/gql".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Replaces `$XDG_CACHE_HOME/kemote`, has to be an absolute path.
+    pub dir: Option<PathBuf>,
+    /// Decoded images kept in memory, see `cache::HashMapImageCache`.
+    pub memory_budget_mb: usize,
+    /// Downloaded images and cached queries kept on disk, see `disk_cache::prune`.
+    pub disk_quota_mb: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            memory_budget_mb: 512,
+            disk_quota_mb: 1024,
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget_mb * 1024 * 1024
+    }
+
+    pub fn disk_quota(&self) -> u64 {
+        self.disk_quota_mb * 1024 * 1024
+    }
+}
+
+impl Config {
+    // Everything the types alone don't catch, all problems are reported at once.
+    fn validate(&self) -> std::result::Result<(), String> {
+        let mut problems = vec![];
+        if self.recent_emotes == 0 {
+            problems.push("recent_emotes has to be at least 1".to_string());
+        }
+        if self.window.width < 200. || self.window.height < 200. {
+            problems.push("window has to be at least 200x200".to_string());
+        }
+        if !(1..=100).contains(&self.search.per_page) {
+            problems.push("search.per_page has to be between 1 and 100".to_string());
+        }
+        if !self.seventv.api_url.starts_with("https://") && !self.seventv.api_url.starts_with("http://") {
+            problems.push(format!("seventv.api_url {:?} is not an http url", self.seventv.api_url));
+        }
+        if let Some(dir) = &self.cache.dir
+            && !dir.is_absolute()
+        {
+            problems.push(format!("cache.dir {:?} is not an absolute path", dir));
+        }
+        if self.cache.memory_budget_mb == 0 || self.cache.disk_quota_mb == 0 {
+            problems.push("cache.memory_budget_mb and cache.disk_quota_mb have to be at least 1".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join(", "))
+        }
+    }
+}
+
+pub fn path() -> PathBuf {
+    xdg::config_home().join(&*APP_NAME).join("config.toml")
+}
+
+/// Modification time of the config file, the config is reloaded whenever it changes.
+pub fn modified() -> Option<SystemTime> {
+    fs::metadata(path()).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The current config, it can be replaced by a reload at any time so it shouldn't be held onto.
+pub fn get() -> Arc<Config> {
+    CONFIG.read().expect("rip config lock").clone()
+}
+
+/// Loads the config at startup. An invalid config is replaced by the defaults, so the picker
+/// still starts, and the error is returned to be shown in the window.
+pub fn init() -> Option<Error> {
+    match load() {
+        Ok(config) => {
+            *CONFIG.write().expect("rip config lock") = Arc::new(config);
+            None
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            Some(err)
+        }
+    }
+}
+
+/// Loads the config again after the file has changed. Returns the previous config along with the
+/// new one, so only what has changed needs to be applied. An invalid config keeps the current one.
+pub fn reload() -> Result<(Arc<Config>, Arc<Config>)> {
+    let config = Arc::new(load()?);
+    let previous = std::mem::replace(&mut *CONFIG.write().expect("rip config lock"), config.clone());
+    Ok((previous, config))
+}
+
+fn load() -> Result<Config> {
+    let path = path();
+    let config = if path.exists() {
+        let contents = fs::read_to_string(&path)?;
+        parse(&contents).map_err(|err| Error::Config(format!("{}: {}", path.display(), err)))?
+    } else {
+        Config::default()
+    };
+    config.validate().map_err(Error::Config)?;
+    Ok(config)
+}
+
+// Toml errors come with a snippet of the file, that doesn't fit into the error bar.
+fn parse(contents: &str) -> std::result::Result<Config, String> {
+    toml::from_str(contents).map_err(|err| match err.span() {
+        Some(span) => format!(
+            "line {}: {}",
+            contents[..span.start].matches('\n').count() + 1,
+            err.message()
+        ),
+        None => err.message().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse(""), Ok(Config::default()));
+    }
+
+    #[test]
+    fn parse_values() {
+        let config = parse("recent_emotes = 5\n[colors]\ntext = \"#ffffff\"\n").unwrap();
+        assert_eq!(config.recent_emotes, 5);
+        assert_eq!(config.colors.text, Color(0xffffff));
+        assert_eq!(config.colors.bar, Colors::default().bar);
+    }
+
+    #[test]
+    fn parse_error_line() {
+        let err = parse("recent_emotes = 5\n\n[colors]\ntext = \"white\"\n").unwrap_err();
+        assert!(err.starts_with("line 4: "), "{}", err);
+        assert!(err.contains("#rrggbb"), "{}", err);
+
+        let err = parse("recent_emotes = 5\nunknown = 1\n").unwrap_err();
+        assert!(err.starts_with("line 2: "), "{}", err);
+    }
+
+    #[test]
+    fn validate_default() {
+        assert_eq!(Config::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_everything() {
+        let config = Config {
+            recent_emotes: 0,
+            search: SearchSettings {
+                per_page: 101,
+                ..SearchSettings::default()
+            },
+            seventv: SevenTvConfig {
+                api_url: "ftp://7tv.app".to_string(),
+            },
+            cache: CacheConfig {
+                dir: Some(PathBuf::from("relative/cache")),
+                ..CacheConfig::default()
+            },
+            ..Config::default()
+        };
+
+        let err = config.validate().unwrap_err();
+        assert!(err.contains("recent_emotes"), "{}", err);
+        assert!(err.contains("search.per_page"), "{}", err);
+        assert!(err.contains("seventv.api_url"), "{}", err);
+        assert!(err.contains("cache.dir"), "{}", err);
+        assert!(!err.contains("window"), "{}", err);
+    }
+}
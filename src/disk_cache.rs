@@ -0,0 +1,273 @@
+use crate::CACHE_DIR;
+use crate::config;
+use crate::error::Result;
+use std::fs::{self, File, FileTimes};
+use std::io::{self, prelude::*};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+// Anything else in the cache dir (e.g. leftovers of older versions) is never evicted.
+const CACHED_DIRS: [&str; 2] = ["webm", "queries"];
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Size of the disk cache, `webm` holds the images and `queries` the search results.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub dirs: Vec<(&'static str, DirStats)>,
+    pub quota: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DirStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl Stats {
+    pub fn total(&self) -> DirStats {
+        self.dirs.iter().fold(DirStats::default(), |total, (_, dir)| DirStats {
+            files: total.files + dir.files,
+            bytes: total.bytes + dir.bytes,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub files: usize,
+    pub bytes: u64,
+    /// Files that couldn't be removed, the prune goes on with the next ones.
+    pub failed: usize,
+}
+
+struct CachedFile {
+    path: PathBuf,
+    bytes: u64,
+    last_access: SystemTime,
+}
+
+/// Marks the cached file as used just now, least recently used files are evicted first.
+///
+/// @NOTE: the access time is set explicitly, most filesystems are mounted with `relatime` or
+///  `noatime` and wouldn't update it on reads. Modification time is left alone, that one is the
+///  time the file was cached.
+pub fn touch(path: impl AsRef<Path>) {
+    // local emotes are not ours to touch
+    if !path.as_ref().starts_with(&*CACHE_DIR) {
+        return;
+    }
+    let result = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_times(FileTimes::new().set_accessed(SystemTime::now())));
+    if let Err(err) = result {
+        println!("FAILED TO TOUCH CACHED FILE: {}", err);
+    }
+}
+
+/// Writes the file through a temporary one that is renamed over the destination once it's fully
+/// written, so a crash or a failed download never leaves a truncated file behind.
+pub fn write_atomic(path: impl AsRef<Path>, contents: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // unique within the process too, the grid and copying can write the same image at once
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let result = File::create(&tmp_path)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if result.is_err() {
+        _ = fs::remove_file(&tmp_path);
+    }
+    Ok(result?)
+}
+
+/// Checks that a cached image isn't truncated, using the sizes and trailers of the formats we
+/// download. Anything else is assumed to be fine.
+pub fn is_complete_image(bytes: &[u8]) -> bool {
+    match bytes {
+        // RIFF header has the size of the rest of the file
+        [b'R', b'I', b'F', b'F', a, b, c, d, b'W', b'E', b'B', b'P', ..] => {
+            u32::from_le_bytes([*a, *b, *c, *d]) as usize + 8 == bytes.len()
+        }
+        [0x89, b'P', b'N', b'G', ..] => bytes.ends_with(&[b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]),
+        [b'G', b'I', b'F', b'8', ..] => bytes.ends_with(&[0x3b]),
+        [] => false,
+        _ => true,
+    }
+}
+
+pub fn stats(quota: u64) -> Stats {
+    Stats {
+        dirs: CACHED_DIRS
+            .iter()
+            .map(|dir| {
+                let files = cached_files(dir);
+                let stats = DirStats {
+                    files: files.len(),
+                    bytes: files.iter().map(|file| file.bytes).sum(),
+                };
+                (*dir, stats)
+            })
+            .collect(),
+        quota,
+    }
+}
+
+/// Evicts the least recently used files until the cache fits into `quota` bytes. Only the files
+/// removed by this prune are reported, another one could be running at the same time (e.g. `kemote
+/// cache prune` while the picker is open).
+pub fn prune(quota: u64) -> PruneReport {
+    let mut files: Vec<CachedFile> = CACHED_DIRS.iter().flat_map(|dir| cached_files(dir)).collect();
+    files.sort_by_key(|file| file.last_access);
+
+    let mut total: u64 = files.iter().map(|file| file.bytes).sum();
+    let mut report = PruneReport::default();
+    for file in files {
+        if total <= quota {
+            break;
+        }
+        match fs::remove_file(&file.path) {
+            Ok(()) => {
+                report.files += 1;
+                report.bytes += file.bytes;
+            }
+            // already removed by someone else, it doesn't take any space either way
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                println!("FAILED TO PRUNE {:?}: {}", file.path, err);
+                report.failed += 1;
+                continue;
+            }
+        }
+        total -= file.bytes;
+    }
+
+    report
+}
+
+fn cached_files(dir: &str) -> Vec<CachedFile> {
+    let Ok(entries) = fs::read_dir(format!("{}/{}", *CACHE_DIR, dir)) else {
+        return vec![];
+    };
+
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            // files still being written by `write_atomic`
+            if !metadata.is_file() || entry.path().extension().is_some_and(|ext| ext == "tmp") {
+                return None;
+            }
+            Some(CachedFile {
+                path: entry.path(),
+                bytes: metadata.len(),
+                // not every filesystem keeps access times, those fall back to the time of caching
+                last_access: metadata.accessed().or_else(|_| metadata.modified()).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Handles `kemote cache <command>`, returns false for anything that isn't a cache command so
+/// the picker itself can be started.
+pub fn run_command(args: &[String]) -> bool {
+    let Some((first, rest)) = args.split_first() else {
+        return false;
+    };
+    if first != "cache" {
+        return false;
+    }
+
+    let quota = config::get().cache.disk_quota();
+    match rest.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["stats"] => {
+            let stats = stats(quota);
+            for (dir, dir_stats) in &stats.dirs {
+                println!(
+                    "{:<8} {:>6} files {:>10}",
+                    dir,
+                    dir_stats.files,
+                    megabytes(dir_stats.bytes)
+                );
+            }
+            let total = stats.total();
+            println!(
+                "{:<8} {:>6} files {:>10} (quota {})",
+                "total",
+                total.files,
+                megabytes(total.bytes),
+                megabytes(stats.quota)
+            );
+        }
+        ["prune"] => {
+            let report = prune(quota);
+            println!("removed {} files, freed {}", report.files, megabytes(report.bytes));
+            if report.failed > 0 {
+                eprintln!("failed to remove {} files", report.failed);
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("usage: kemote cache <stats|prune>");
+            std::process::exit(2);
+        }
+    }
+    true
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1024. / 1024.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webp(payload: &[u8], size: u32) -> Vec<u8> {
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(b"WEBP");
+        bytes.extend(payload);
+        bytes
+    }
+
+    #[test]
+    fn webp_size() {
+        assert!(is_complete_image(&webp(b"VP8 ", 8)));
+        assert!(!is_complete_image(&webp(b"VP8 ", 12)));
+        assert!(!is_complete_image(&webp(b"VP8 ", 4)));
+    }
+
+    #[test]
+    fn png_trailer() {
+        let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        assert!(!is_complete_image(&png));
+        png.extend([0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+        assert!(is_complete_image(&png));
+    }
+
+    #[test]
+    fn gif_trailer() {
+        assert!(is_complete_image(b"GIF89a\x00\x3b"));
+        assert!(!is_complete_image(b"GIF89a\x00"));
+    }
+
+    #[test]
+    fn empty_and_unknown() {
+        assert!(!is_complete_image(&[]));
+        assert!(is_complete_image(b"\x00\x00\x00\x1cftypavif"));
+    }
+}
@@ -0,0 +1,28 @@
+use thiserror::Error;
+
+/// Everything that can go wrong while searching, caching and copying emotes. Those are shown in
+/// the window instead of crashing it.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("network error: {0}")]
+    Network(String),
+    #[error("unexpected response from {0}: {1}")]
+    Api(&'static str, String),
+    #[error("could not decode image: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not copy into clipboard: {0}")]
+    Clipboard(#[from] wl_clipboard_rs::copy::Error),
+    #[error("invalid config: {0}")]
+    Config(String),
+}
+
+impl Error {
+    /// Network errors mean that we can't reach the provider at all, so we switch to offline mode.
+    pub fn is_network(&self) -> bool {
+        matches!(self, Error::Network(_))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
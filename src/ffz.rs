@@ -0,0 +1,132 @@
+use crate::config;
+use crate::error::{Error, Result};
+use crate::net;
+use crate::provider::{Emote, EmoteProvider, Page, SearchQuery, Sort, Variant, read_body};
+use futures::{FutureExt, future::BoxFuture};
+use gpui::http_client::Url;
+use serde::Deserialize;
+use serde_json;
+use std::collections::HashMap;
+
+pub const PROVIDER_ID: &str = "ffz";
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    id: u64,
+    name: String,
+    // scale ("1", "2", "4") -> url, not every emote has all of the scales
+    urls: HashMap<String, String>,
+    animated: Option<HashMap<String, String>>,
+    // width of the 1x image, the other scales are multiples of it
+    width: Option<u32>,
+    // modifier emotes are drawn on top of the previous one, same as zero-width on 7tv
+    #[serde(default)]
+    modifier: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    emoticons: Vec<Item>,
+    #[serde(rename(deserialize = "_pages"))]
+    pages: usize,
+}
+
+pub struct Ffz;
+
+impl EmoteProvider for Ffz {
+    fn id(&self) -> &'static str {
+        PROVIDER_ID
+    }
+
+    fn search(&self, query: SearchQuery) -> BoxFuture<'static, Result<Page>> {
+        query_ffz(query).boxed()
+    }
+
+    fn image_url(&self, id: &str) -> String {
+        // @NOTE: static ffz emotes are png only and don't have an extension in the url.
+        format!("https://cdn.frankerfacez.com/emote/{}/4", id)
+    }
+}
+
+// ffz has no trending, so those are sorted by the usage count same as top all time
+fn sort(sort: Sort) -> &'static str {
+    match sort {
+        Sort::Newest => "created-desc",
+        Sort::Alphabetical => "name-asc",
+        _ => "count-desc",
+    }
+}
+
+pub async fn query_ffz(query: SearchQuery) -> Result<Page> {
+    let page = query.page.to_string();
+    let per_page = config::get().search.per_page.to_string();
+    let url = Url::parse_with_params(
+        "https://api.frankerfacez.com/v1/emotes",
+        &[
+            ("q", query.text.as_str()),
+            ("sort", sort(query.sort)),
+            ("page", page.as_str()),
+            ("per_page", per_page.as_str()),
+        ],
+    )
+    .expect("rip ffz url");
+
+    let response = net::CLIENT.get(url.as_str()).await?;
+    let raw_response = read_body(PROVIDER_ID, &response)?;
+
+    let data = serde_json::from_str::<Data>(&raw_response).map_err(|e| Error::Api(PROVIDER_ID, e.to_string()))?;
+
+    let emotes = data
+        .emoticons
+        .into_iter()
+        .map(|emote| {
+            let mut variants = scale_variants(&emote.urls, "image/png", "", emote.width);
+            // Animated versions are webp, so we ask for the extension explicitly to store them
+            // correctly in the disk cache.
+            if let Some(animated) = &emote.animated {
+                variants.extend(scale_variants(animated, "image/webp", ".webp", emote.width));
+            }
+            let url = Variant::best(&variants)
+                .map(|variant| variant.url.clone())
+                .unwrap_or_else(|| Ffz.image_url(&emote.id.to_string()));
+
+            Emote {
+                provider: PROVIDER_ID.to_string(),
+                id: emote.id.to_string(),
+                name: emote.name,
+                url,
+                animated: emote.animated.is_some(),
+                zero_width: emote.modifier,
+                variants,
+            }
+        })
+        .collect();
+
+    Ok(Page {
+        emotes,
+        has_more: query.page < data.pages,
+    })
+}
+
+fn scale_variants(urls: &HashMap<String, String>, mime: &str, extension: &str, width: Option<u32>) -> Vec<Variant> {
+    let mut variants: Vec<Variant> = urls
+        .iter()
+        .filter_map(|(scale, url)| {
+            let url = match url.strip_prefix("//") {
+                // older api responses have protocol-relative urls
+                Some(url) => format!("https://{}", url),
+                None => url.clone(),
+            };
+            let scale = scale.parse().ok()?;
+            Some(Variant {
+                url: format!("{}{}", url, extension),
+                mime: mime.to_string(),
+                scale,
+                width: width.map(|width| width * scale),
+                frame_count: None,
+            })
+        })
+        .collect();
+    variants.sort_by_key(|variant| variant.scale);
+    variants
+}
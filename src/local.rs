@@ -0,0 +1,150 @@
+use crate::config;
+use crate::error::Result;
+use crate::provider::{Emote, EmoteProvider, Page, SearchQuery, Variant};
+use futures::{FutureExt, future::BoxFuture};
+use image::codecs::webp::WebPDecoder;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+pub const PROVIDER_ID: &str = "local";
+
+const EXTENSIONS: [&str; 3] = ["png", "gif", "webp"];
+
+/// Emotes from local directories, searched by file name. Directories are taken from
+/// `local_dirs` in the config.
+pub struct Local {
+    dirs: Vec<PathBuf>,
+    // Every image in the dirs, read on the first search. Changing `local_dirs` creates a new
+    // provider, files added to the dirs in the meantime show up only after a restart.
+    index: Arc<OnceLock<Vec<Emote>>>,
+}
+
+impl Local {
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self {
+            dirs,
+            index: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn from_config() -> Option<Self> {
+        let dirs = config::get().local_dirs.clone();
+        (!dirs.is_empty()).then(|| Self::new(dirs))
+    }
+}
+
+impl EmoteProvider for Local {
+    fn id(&self) -> &'static str {
+        PROVIDER_ID
+    }
+
+    fn search(&self, query: SearchQuery) -> BoxFuture<'static, Result<Page>> {
+        let dirs = self.dirs.clone();
+        let index = self.index.clone();
+        async move {
+            // everything matching is returned at once (always sorted by path), there is no point
+            // in paging or sorting local files
+            if query.page > 1 {
+                return Ok(Page::default());
+            }
+
+            // Walking the dirs and reading the webp headers blocks, so it's kept off the ui thread,
+            // later searches only wait for the first one to finish.
+            let emotes = smol::unblock(move || {
+                index
+                    .get_or_init(|| index_local(&dirs))
+                    .iter()
+                    .filter(|emote| emote.name.to_lowercase().contains(&query.text))
+                    .cloned()
+                    .collect()
+            })
+            .await;
+            Ok(Page {
+                emotes,
+                has_more: false,
+            })
+        }
+        .boxed()
+    }
+
+    fn image_url(&self, id: &str) -> String {
+        // id is already an absolute path to the file
+        id.to_string()
+    }
+
+    fn remote(&self) -> bool {
+        false
+    }
+}
+
+fn index_local(dirs: &[PathBuf]) -> Vec<Emote> {
+    let mut files = vec![];
+    for dir in dirs {
+        collect_images(dir, &mut files);
+    }
+    files.sort();
+
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let name = file.file_stem()?.to_str()?.to_string();
+            let path = fs::canonicalize(&file).ok()?.to_str()?.to_string();
+            let extension = file.extension()?.to_str()?.to_lowercase();
+            Some(Emote {
+                provider: PROVIDER_ID.to_string(),
+                animated: is_animated(&file),
+                zero_width: false,
+                // the file itself is the only variant there is
+                variants: vec![Variant {
+                    url: path.clone(),
+                    mime: format!("image/{}", extension),
+                    scale: 1,
+                    width: None,
+                    frame_count: None,
+                }],
+                url: path.clone(),
+                id: path,
+                name,
+            })
+        })
+        .collect()
+}
+
+// Only the header is read for webp, gifs are just assumed to be animated.
+fn is_animated(path: &Path) -> bool {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+    {
+        Some(ext) if ext == "gif" => true,
+        Some(ext) if ext == "webp" => File::open(path)
+            .ok()
+            .and_then(|f| WebPDecoder::new(BufReader::new(f)).ok())
+            .is_some_and(|decoder| decoder.has_animation()),
+        _ => false,
+    }
+}
+
+fn collect_images(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        println!("SKIPPING LOCAL DIR: {:?}", dir);
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // symlinked dirs are not followed, a link back up the tree would recurse forever
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            collect_images(&path, files);
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        {
+            files.push(path);
+        }
+    }
+}
@@ -1,141 +1,188 @@
+mod bttv;
 mod cache;
+mod clipboard;
+mod config;
+mod disk_cache;
+mod error;
+mod ffz;
+mod local;
+mod net;
+mod provider;
 mod seventv;
+mod transcode;
+mod xdg;
 
+use clipboard::OutputSize;
+use config::Config;
 use gpui::{
-    App, AppContext, Application, Bounds, Context, CursorStyle, ElementId, ElementInputHandler, Entity,
-    EntityInputHandler, FocusHandle, Focusable, GlobalElementId, KeyBinding, LayoutId, MouseButton, MouseUpEvent,
-    PaintQuad, Pixels, ShapedLine, SharedString, Style, TextRun, UTF16Selection, UnderlineStyle, Window, WindowBounds,
-    WindowOptions, actions, black, div, fill, hsla, image_cache, img, point, prelude::*, px, relative, rgb, rgba, size,
+    App, AppContext, Application, Bounds, Context, CursorStyle, Div, ElementId, ElementInputHandler, Entity,
+    EntityInputHandler, FocusHandle, Focusable, GlobalElementId, ImageSource, KeyBinding, KeyDownEvent, LayoutId,
+    MouseButton, MouseUpEvent, PaintQuad, Pixels, RenderImage, ScrollHandle, ScrollWheelEvent, ShapedLine,
+    SharedString, Style, Task, TextRun, UTF16Selection, UnderlineStyle, Window, WindowBounds, WindowOptions, actions,
+    black, div, fill, hsla, image_cache, img, point, prelude::*, px, relative, rgb, rgba, size,
 };
-use image::{AnimationDecoder, DynamicImage, Rgba, codecs::webp::WebPDecoder};
 use lazy_static::lazy_static;
-use seventv::WebmEmote;
-use std::collections::VecDeque;
+use provider::{Emote, EmoteProvider, Filters, Providers, SearchQuery, SearchResults, Sort};
+use std::collections::{HashSet, VecDeque};
 use std::env;
-use std::fs::{self, File};
-use std::io::{BufReader, Cursor, prelude::*};
+use std::fs;
+use std::io;
 use std::ops::Range;
 use std::path::PathBuf;
-use std::sync::{Arc, atomic};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use unicode_segmentation::*;
 use util::truncate_to_byte_limit;
-use wl_clipboard_rs::copy::{ClipboardType, MimeSource, MimeType, Options, Source};
 
-actions!(text_input, [Backspace, Escape, CtrlSpace, CtrlS]);
+actions!(
+    text_input,
+    [
+        Backspace, Escape, CtrlSpace, CtrlS, CtrlO, CtrlN, CtrlT, CtrlA, CtrlZ, CtrlG, CtrlE, CtrlR
+    ]
+);
+actions!(emote_grid, [Left, Right, Up, Down, Enter, FocusSearch]);
 
 lazy_static! {
     static ref APP_NAME: String = String::from(if cfg!(debug_assertions) { "dev-kemote" } else { "kemote" });
-    static ref CACHE_DIR: String = format!("{}/.cache/{}", env::var("HOME").unwrap(), *APP_NAME);
+    // the config has to be loaded before this is used for the first time
+    static ref CACHE_DIR: String = match &config::get().cache.dir {
+        Some(dir) => dir.display().to_string(),
+        None => xdg::cache_home().join(&*APP_NAME).display().to_string(),
+    };
+    // recent emotes, kept apart from the cache so they survive clearing it
+    static ref STATE_DIR: String = xdg::state_home().join(&*APP_NAME).display().to_string();
 }
 const VERSION: &str = "0.2.0"; // keep in sync with Cargo.toml
 
 #[derive(Debug, Clone)]
 struct DisplayedEmote {
-    emote: seventv::WebmEmote,
+    emote: Emote,
+    // Animation playback, stepped the same way as in the gpui img element, which keeps its own
+    // frame counter private. See `step_animation`.
+    frame_index: usize,
+    last_frame_time: Option<Instant>,
+    // Offset into the animation of the frame that was painted last, shift-click copies that one.
+    shown_at: Duration,
 }
 
 impl DisplayedEmote {
-    fn on_mouse_up(&mut self, _: &MouseUpEvent, window: &mut Window, cx: &mut Context<Self>) {
+    fn new(emote: Emote) -> Self {
+        Self {
+            emote,
+            frame_index: 0,
+            last_frame_time: None,
+            shown_at: Duration::ZERO,
+        }
+    }
+
+    // gpui advances at most one frame per paint, once the current frame has been shown for its
+    // delay, and the img asks for the next paint of this view while it's animating. Doing the same
+    // on every render keeps both counters on the same frame, even when painting falls behind.
+    fn step_animation(&mut self, image: &RenderImage) {
+        let frame_count = image.frame_count();
+        if frame_count < 2 {
+            return;
+        }
+
+        // gpui paints the frame from before the step
+        let shown_frame = self.frame_index;
+        let now = Instant::now();
+        match self.last_frame_time {
+            Some(last_frame_time) => {
+                let elapsed = now - last_frame_time;
+                let frame_duration = Duration::from(image.delay(self.frame_index));
+                if elapsed >= frame_duration {
+                    self.frame_index = (self.frame_index + 1) % frame_count;
+                    self.last_frame_time = Some(now - (elapsed - frame_duration));
+                }
+            }
+            None => self.last_frame_time = Some(now),
+        }
+
+        // The middle of the frame, the full size image it's copied from doesn't have to have the
+        // same frames as the thumbnail.
+        let frame_start: Duration = (0..shown_frame).map(|frame| Duration::from(image.delay(frame))).sum();
+        self.shown_at = frame_start + Duration::from(image.delay(shown_frame)) / 2;
+    }
+
+    fn loaded_image(&self, window: &mut Window, cx: &mut Context<Self>) -> Option<Arc<RenderImage>> {
+        let ImageSource::Resource(resource) = self.emote.image_source() else {
+            return None;
+        };
+        let image_cache = window.root::<InputExample>()??.read(cx).image_cache.clone();
+        image_cache.update(cx, |image_cache, _| image_cache.loaded(&resource))
+    }
+
+    fn on_mouse_up(&mut self, event: &MouseUpEvent, window: &mut Window, cx: &mut Context<Self>) {
         println!("CLICKED EMOTE: {:?}", &self.emote);
 
-        let window_root = window
-            .root::<InputExample>()
-            .expect("rip unwrap 1")
-            .expect("rip unwrap 2");
+        // shift-click captures the frame on screen instead of the whole emote
+        let frame = event.modifiers.shift.then_some(self.shown_at);
+
+        let Some(Some(window_root)) = window.root::<InputExample>() else {
+            return;
+        };
 
         window_root.update(cx, |view, cx| {
-            view.text_input.update(cx, |tinput, _cx| {
-                // @TODO: To make window re-render recently clicked emotes, we would need to
-                //  actually have some way to track if those are currently being rendered vs
-                //  specific search emojies being rendered. The simplest way would probably be a
-                //  'recent emotes' state (bool?) so instead of copying recent emotes into emotes
-                //  array, we would just render from emotes array if recent emotes is true. Only
-                //  then this change could be updated in real time.
-                tinput.recent_emotes.access(self.emote.clone());
-                println!("NEW SIZE OF RECENT EMOTES: {:?}", tinput.recent_emotes.emotes.len());
-            });
+            view.text_input
+                .update(cx, |tinput, cx| tinput.copy_emote(self.emote.clone(), frame, cx));
         });
+    }
 
-        let f = File::open(PathBuf::from(WebmEmote::path(&self.emote.url))).expect("rip opening emotes path");
-        let mut webp_decoder = WebPDecoder::new(BufReader::new(f)).expect("rip webp decoder");
-        let mut buffer: Vec<u8> = Vec::new();
-
-        let final_path: String;
-        if webp_decoder.has_animation() {
-            webp_decoder
-                .set_background_color(Rgba([0, 0, 0, 0]))
-                .expect("rip webp decoder");
-            webp_decoder
-                .into_frames()
-                // @TODO: we take first frame right now, but we should allow getting static webp
-                //  sticker out of any frame that you picked by clicking at the played webp, but
-                //  for that obviously need to get a webp player state frame-perfect during this
-                //  execution. This would be really nice though and much more usable than current.
-                .next()
-                .expect("rip no first webp frame")
-                .expect("rip error on first webp frame")
-                .buffer()
-                .write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
-                .expect("rip convert and write to buffer");
-
-            // @TODO: We have to create a tmp fake file with the single frame for telegram to recognize
-            //  the format correctly. I'm not sure how hackable it is, we could try to fake it and lie
-            //  to telegram somehow, but this is probably fine for the future. In the future might want
-            //  to create this file in the persistent cache during download.
-            final_path = format!("/tmp/{}.webp", self.emote.id.clone());
-            File::create(final_path.clone())
-                .expect("rip tmp file")
-                .write_all(&buffer)
-                .expect("rip write file");
-        } else {
-            final_path = WebmEmote::path(&self.emote.url);
-            DynamicImage::from_decoder(webp_decoder)
-                .expect("rip decoding static webp")
-                .to_rgba8()
-                .write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
-                .expect("rip write bytes");
-        }
+    fn on_right_mouse_up(&mut self, _: &MouseUpEvent, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(Some(window_root)) = window.root::<InputExample>() else {
+            return;
+        };
 
-        let mut opts = Options::new();
-        opts.omit_additional_text_mime_types(true); // do not add default mimetypes
-        opts.clipboard(ClipboardType::Both);
-        opts.copy_multi(vec![
-            MimeSource {
-                source: Source::Bytes(buffer.into()),
-                mime_type: MimeType::Specific("image/webp".to_string()),
-            },
-            MimeSource {
-                source: Source::Bytes(format!("file://{}", &final_path).into_bytes().into()),
-                mime_type: MimeType::Specific("text/x-moz-url".to_string()),
-            },
-        ])
-        .expect("rip multi-copy into clipboard");
+        window_root.update(cx, |view, cx| {
+            view.text_input
+                .update(cx, |tinput, cx| tinput.save_emote_as(self.emote.clone(), cx));
+        });
     }
 }
 
+// Hardcoded emote shown while the actual one is loading.
+fn loading_image_url() -> String {
+    seventv::SevenTv.image_url("01F79PC23G0000DRDGH5T4QFMA")
+}
+
 impl Render for DisplayedEmote {
-    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        if self.emote.animated
+            && let Some(image) = self.loaded_image(window, cx)
+        {
+            self.step_animation(&image);
+        }
+
         div()
             .max_w_20()
-            .ml_6()
-            .mt_6()
             .text_center()
             .child(
-                img(self.emote.url.clone())
+                img(self.emote.image_source())
                     .max_w_20()
                     .max_h_20()
                     .object_fit(gpui::ObjectFit::Contain)
                     .on_mouse_up(MouseButton::Left, cx.listener(Self::on_mouse_up))
+                    .on_mouse_up(MouseButton::Right, cx.listener(Self::on_right_mouse_up))
                     .id("webp")
                     // Default loading element (hardcoded emote for now) ...
                     .with_loading(|| {
-                        img("https://cdn.7tv.app/emote/01F79PC23G0000DRDGH5T4QFMA/4x.webp")
+                        img(loading_image_url())
                             .max_w_20()
                             .max_h_20()
                             .object_fit(gpui::ObjectFit::Contain)
                             .id("webp")
                             .into_any_element()
+                    })
+                    // Broken images shouldn't break the rest of the grid, show a placeholder instead.
+                    .with_fallback(|| {
+                        div()
+                            .size_20()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .child("?")
+                            .into_any_element()
                     }),
             )
             .child(
@@ -152,7 +199,7 @@ impl Render for DisplayedEmote {
 
 #[derive(Debug)]
 struct RecentEmotes {
-    emotes: VecDeque<seventv::WebmEmote>,
+    emotes: VecDeque<Emote>,
     capacity: usize,
 }
 
@@ -163,21 +210,20 @@ impl RecentEmotes {
             capacity,
         };
 
-        let recent_fp = format!("{}/recent.json", *CACHE_DIR);
-        if let Ok(mut file) = File::open(recent_fp.clone()) {
-            let mut contents = String::new();
-            file.read_to_string(&mut contents).expect("rip file read");
-            let emotes: Vec<seventv::WebmEmote> = serde_json::from_str(&contents).expect("rip json load");
-            for emote in emotes {
-                recent_emotes.emotes.push_back(emote);
+        // Broken recent emotes are not worth failing the startup, we just start from scratch.
+        let recent_fp = format!("{}/recent.json", *STATE_DIR);
+        if let Ok(contents) = fs::read_to_string(recent_fp.clone()) {
+            match serde_json::from_str::<Vec<Emote>>(&contents) {
+                Ok(emotes) => recent_emotes.emotes.extend(emotes),
+                Err(err) => println!("FAILED TO LOAD RECENT EMOTES: {}", err),
             }
         }
 
         recent_emotes
     }
 
-    pub fn access(&mut self, emote: seventv::WebmEmote) {
-        if let Some(pos) = self.emotes.iter().position(|e| e == &emote) {
+    pub fn access(&mut self, emote: Emote) -> error::Result<()> {
+        if let Some(pos) = self.emotes.iter().position(|e| e.same(&emote)) {
             self.emotes.remove(pos);
         }
 
@@ -187,14 +233,19 @@ impl RecentEmotes {
 
         self.emotes.push_front(emote);
 
-        let recent_fp = format!("{}/recent.json", *CACHE_DIR);
-        if let Ok(mut file) = File::create(recent_fp.clone()) {
-            file.write_all(serde_json::to_vec_pretty(&self.emotes).unwrap().as_ref())
-                .expect("rip write file");
-        }
+        let recent_fp = format!("{}/recent.json", *STATE_DIR);
+        disk_cache::write_atomic(
+            recent_fp,
+            &serde_json::to_vec_pretty(&self.emotes).map_err(io::Error::from)?,
+        )
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.emotes.truncate(capacity);
     }
 
-    pub fn recent(&self) -> impl Iterator<Item = &seventv::WebmEmote> {
+    pub fn recent(&self) -> impl Iterator<Item = &Emote> {
         self.emotes.iter()
     }
 }
@@ -210,7 +261,24 @@ struct TextInput {
     last_bounds: Option<Bounds<Pixels>>,
     emotes: Vec<Entity<DisplayedEmote>>,
     recent_emotes: RecentEmotes,
-    last_active: Arc<atomic::AtomicBool>,
+    providers: Providers,
+    offline: bool,
+    // Set by the last search when no provider could be reached. Unlike `offline` it's not sticky,
+    // every search tries the network again.
+    went_offline: bool,
+    error: Option<SharedString>,
+    page: usize,
+    has_more: bool,
+    loading_more: bool,
+    sort: Sort,
+    filters: Filters,
+    selected: Option<usize>,
+    copy_animated: bool,
+    output_size: OutputSize,
+    // Dropping a task cancels it, along with the requests it's waiting on, so there is only ever
+    // one search (and one page load) running for the current content of the input.
+    search_task: Option<Task<()>>,
+    load_more_task: Option<Task<()>>,
 }
 
 impl TextInput {
@@ -222,14 +290,17 @@ impl TextInput {
     }
 
     fn show_recent_emotes(&mut self, _: &CtrlSpace, _window: &mut Window, cx: &mut Context<Self>) {
+        self.cancel_search();
         cx.spawn(async move |entity, cx| {
             entity
                 .update(cx, |new_self, cx| {
-                    let emotes: Vec<seventv::WebmEmote> = new_self.recent_emotes.recent().cloned().collect();
+                    let emotes: Vec<Emote> = new_self.recent_emotes.recent().cloned().collect();
 
                     new_self.emotes.clear();
+                    new_self.selected = None;
+                    new_self.has_more = false;
                     for emote in emotes {
-                        new_self.emotes.push(cx.new(|_cx| DisplayedEmote { emote }));
+                        new_self.emotes.push(cx.new(|_cx| DisplayedEmote::new(emote)));
                     }
                     cx.notify();
                 })
@@ -240,22 +311,368 @@ impl TextInput {
 
     fn clear_input(&mut self, _: &CtrlS, _window: &mut Window, cx: &mut Context<Self>) {
         self.reset();
+        self.cancel_search();
 
         cx.spawn(async move |entity, cx| {
             entity
                 .update(cx, |new_self, cx| {
-                    let emotes: Vec<seventv::WebmEmote> = new_self.recent_emotes.recent().cloned().collect();
+                    let emotes: Vec<Emote> = new_self.recent_emotes.recent().cloned().collect();
 
                     new_self.emotes.clear();
+                    new_self.selected = None;
+                    new_self.has_more = false;
                     for emote in emotes {
-                        new_self.emotes.push(cx.new(|_cx| DisplayedEmote { emote }));
+                        new_self.emotes.push(cx.new(|_cx| DisplayedEmote::new(emote)));
+                    }
+                    cx.notify();
+                })
+                .expect("rip updating text_input");
+        })
+        .detach();
+
+        cx.notify();
+    }
+
+    fn next_sort(&mut self, _: &CtrlT, _window: &mut Window, cx: &mut Context<Self>) {
+        self.cycle_sort(cx);
+    }
+
+    fn cycle_animation_filter(&mut self, _: &CtrlA, _window: &mut Window, cx: &mut Context<Self>) {
+        self.filters.animation = self.filters.animation.next();
+        self.search(cx);
+        cx.notify();
+    }
+
+    fn toggle_zero_width_filter(&mut self, _: &CtrlZ, _window: &mut Window, cx: &mut Context<Self>) {
+        self.filters.exclude_zero_width = !self.filters.exclude_zero_width;
+        self.search(cx);
+        cx.notify();
+    }
+
+    /// Copies the emote into the clipboard, or only the frame shown at `frame` into the animation.
+    fn copy_emote(&mut self, emote: Emote, frame: Option<Duration>, cx: &mut Context<Self>) {
+        let animated = self.copy_animated;
+        let size = self.output_size;
+        let copied = emote.clone();
+
+        cx.spawn(async move |entity, cx| {
+            // the grid only has thumbnails, the full size image is downloaded right before copying
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    let emote = cache::fetch_full_size(emote).await?;
+                    match frame {
+                        Some(elapsed) => clipboard::copy_frame(&emote, elapsed, size),
+                        None => clipboard::copy_emote(&emote, animated, size),
+                    }
+                })
+                .await;
+
+            entity
+                .update(cx, |new_self, cx| {
+                    if let Err(err) = result {
+                        println!("FAILED TO COPY EMOTE: {}", err);
+                        new_self.error = Some(err.to_string().into());
+                        cx.notify();
+                        return;
+                    }
+
+                    // @TODO: To make window re-render recently clicked emotes, we would need to
+                    //  actually have some way to track if those are currently being rendered vs
+                    //  specific search emojies being rendered. The simplest way would probably be a
+                    //  'recent emotes' state (bool?) so instead of copying recent emotes into emotes
+                    //  array, we would just render from emotes array if recent emotes is true. Only
+                    //  then this change could be updated in real time.
+                    new_self.error = new_self
+                        .recent_emotes
+                        .access(copied)
+                        .err()
+                        .map(|err| err.to_string().into());
+                    println!("NEW SIZE OF RECENT EMOTES: {:?}", new_self.recent_emotes.emotes.len());
+                    cx.notify();
+                })
+                .expect("rip updating text_input");
+        })
+        .detach();
+    }
+
+    /// Asks for a file to save the emote into, the format is picked by the file extension so the
+    /// emote can be exported as a gif.
+    fn save_emote_as(&mut self, emote: Emote, cx: &mut Context<Self>) {
+        let directory = PathBuf::from(env::var("HOME").unwrap_or_default());
+        let prompt = cx.prompt_for_new_path(&directory);
+
+        cx.spawn(async move |entity, cx| {
+            let path = match prompt.await {
+                Ok(Ok(Some(path))) => path,
+                Ok(Err(err)) => {
+                    println!("FAILED TO PROMPT FOR PATH: {}", err);
+                    return;
+                }
+                _ => return, // cancelled
+            };
+
+            // transcoding could take a while for longer animations, don't block the window
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    let emote = cache::fetch_full_size(emote).await?;
+                    transcode::export(&emote, &path)
+                })
+                .await;
+
+            entity
+                .update(cx, |new_self, cx| {
+                    match result {
+                        Ok(path) => println!("SAVED EMOTE: {:?}", path),
+                        Err(err) => new_self.error = Some(err.to_string().into()),
                     }
                     cx.notify();
                 })
                 .expect("rip updating text_input");
         })
         .detach();
+    }
+
+    /// Saves the emote at `ix` in the grid, if there is one.
+    fn save_nth(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if let Some(emote) = self.emotes.get(ix) {
+            let emote = emote.read(cx).emote.clone();
+            self.save_emote_as(emote, cx);
+        }
+    }
+
+    /// Copies the emote at `ix` in the grid, if there is one.
+    fn copy_nth(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if let Some(emote) = self.emotes.get(ix) {
+            let emote = emote.read(cx).emote.clone();
+            self.selected = Some(ix);
+            self.copy_emote(emote, None, cx);
+        }
+    }
+
+    fn toggle_copy_animated(&mut self, _: &CtrlG, _window: &mut Window, cx: &mut Context<Self>) {
+        self.copy_animated = !self.copy_animated;
+        cx.notify();
+    }
+
+    fn next_output_size(&mut self, _: &CtrlR, _window: &mut Window, cx: &mut Context<Self>) {
+        self.output_size = self.output_size.next();
+        cx.notify();
+    }
+
+    fn next_page(&mut self, _: &CtrlN, _window: &mut Window, cx: &mut Context<Self>) {
+        self.load_more(cx);
+    }
+
+    fn toggle_offline(&mut self, _: &CtrlO, _window: &mut Window, cx: &mut Context<Self>) {
+        self.offline = !self.offline;
+        self.search(cx);
+        cx.notify();
+    }
+
+    // Searches for the current content of the input. Matching recent and cached emotes are shown
+    // right away, providers are queried once the typing stops (remote ones only for long enough
+    // queries). The previous search is cancelled, whether it's still waiting for the debounce or
+    // already for the providers.
+    fn search(&mut self, cx: &mut Context<Self>) {
+        let query = self.query(1);
+        self.cancel_search();
+        self.page = 1;
+        self.has_more = false;
+
+        let providers = self.providers.clone();
+        let offline = self.offline;
+        let settings = config::get().search;
+        self.search_task = Some(cx.spawn(async move |entity, cx| {
+            println!("Potential query: {:?}", query);
+            let empty = query.is_empty();
+
+            // Only the index of cached emotes is searched while typing, anything else waits for the
+            // debounce. Checking which of those were downloaded still stats the files, so that's
+            // not done on the ui thread. Cached emotes don't know their tags, there is nothing to
+            // show for those.
+            if !empty && query.tags.is_empty() {
+                let instant_query = query.clone();
+                let cached = cx
+                    .background_executor()
+                    .spawn(async move { provider::search_cached_emotes(&instant_query, settings.per_page) })
+                    .await;
+                entity
+                    .update(cx, |new_self, cx| {
+                        let instant = SearchResults {
+                            emotes: cached,
+                            ..Default::default()
+                        };
+                        let instant = new_self.with_recent_emotes(&query, instant);
+                        new_self.emotes.clear();
+                        new_self.selected = None;
+                        new_self.show_results(instant, cx);
+                    })
+                    .expect("rip updating text_input");
+            }
+
+            cx.background_executor().timer(settings.debounce()).await;
+
+            // short queries are searched only through local dirs and the cache
+            let offline = offline || !settings.is_remote(&query);
+
+            let mut results = SearchResults::default();
+            if !empty {
+                results = providers.search(query.clone(), offline).await;
+            }
+            let stale = results.stale && !offline;
+
+            entity
+                .update(cx, |new_self, cx| {
+                    if empty {
+                        results.emotes = new_self.recent_emotes.recent().cloned().collect();
+                    } else if query.tags.is_empty() {
+                        // same order as the instant results, so the grid doesn't shuffle
+                        results = new_self.with_recent_emotes(&query, results);
+                    }
+
+                    new_self.emotes.clear();
+                    new_self.selected = None;
+                    new_self.show_results(results, cx);
+                })
+                .expect("rip updating text_input");
+
+            // Stale cached results are shown right away, and replaced once the fresh ones are back.
+            if !stale {
+                return;
+            }
+            let Some(mut fresh) = providers.revalidate(query.clone()).await else {
+                return;
+            };
+
+            entity
+                .update(cx, |new_self, cx| {
+                    // with more pages loaded the refreshed cache is only used the next time
+                    if new_self.page != 1 {
+                        return;
+                    }
+                    if query.tags.is_empty() {
+                        fresh = new_self.with_recent_emotes(&query, fresh);
+                    }
+
+                    let unchanged = new_self.emotes.len() == fresh.emotes.len()
+                        && new_self
+                            .emotes
+                            .iter()
+                            .zip(&fresh.emotes)
+                            .all(|(shown, emote)| &shown.read(cx).emote == emote);
+                    if unchanged {
+                        return;
+                    }
+
+                    println!("REVALIDATED QUERY CHANGED");
+                    new_self.emotes.clear();
+                    new_self.selected = None;
+                    new_self.show_results(fresh, cx);
+                })
+                .expect("rip updating text_input");
+        }));
+    }
+
+    // Recent emotes matching the query go first, those are the most likely ones to be picked.
+    fn with_recent_emotes(&self, query: &SearchQuery, mut results: SearchResults) -> SearchResults {
+        let mut emotes: Vec<Emote> = self
+            .recent_emotes
+            .recent()
+            .filter(|emote| emote.name.to_lowercase().contains(&query.text) && query.filters.matches(emote))
+            .cloned()
+            .collect();
+        results
+            .emotes
+            .retain(|emote| !emotes.iter().any(|recent| recent.same(emote)));
+        emotes.append(&mut results.emotes);
+        results.emotes = emotes;
+        results
+    }
+
+    // Only the settings copied into the input are applied here, everything else reads the config
+    // whenever it's needed.
+    fn apply_config(&mut self, result: error::Result<(Arc<Config>, Arc<Config>)>, cx: &mut Context<Self>) {
+        let (previous, config) = match result {
+            Ok(configs) => configs,
+            Err(err) => {
+                println!("FAILED TO RELOAD CONFIG: {}", err);
+                self.error = Some(err.to_string().into());
+                cx.notify();
+                return;
+            }
+        };
 
+        println!("CONFIG RELOADED");
+        self.error = None;
+        self.recent_emotes.set_capacity(config.recent_emotes);
+        // the size picked in the picker is kept, unless it was changed in the config as well
+        if previous.output_size != config.output_size {
+            self.output_size = config.output_size;
+        }
+        if previous.local_dirs != config.local_dirs {
+            self.providers = Providers::builtin();
+            self.search(cx);
+        }
+        cx.notify();
+    }
+
+    // Drops the running search and page load, their results are no longer wanted.
+    fn cancel_search(&mut self) {
+        self.search_task = None;
+        self.load_more_task = None;
+        self.loading_more = false;
+    }
+
+    // Loads the next page of the current search, results are appended to the grid.
+    fn load_more(&mut self, cx: &mut Context<Self>) {
+        if !self.has_more || self.loading_more {
+            return;
+        }
+        self.loading_more = true;
+
+        let query = self.query(self.page + 1);
+        let providers = self.providers.clone();
+        let offline = self.offline;
+        self.load_more_task = Some(cx.spawn(async move |entity, cx| {
+            println!("Loading more: {:?}", query);
+            let page = query.page;
+            let results = providers.search(query, offline).await;
+
+            entity
+                .update(cx, |new_self, cx| {
+                    new_self.page = page;
+                    new_self.loading_more = false;
+                    new_self.show_results(results, cx);
+                })
+                .expect("rip updating text_input");
+        }));
+    }
+
+    fn query(&self, page: usize) -> SearchQuery {
+        let input = truncate_to_byte_limit(&self.content, 64).to_lowercase();
+        SearchQuery::parse(&input, page, self.sort, self.filters)
+    }
+
+    fn cycle_sort(&mut self, cx: &mut Context<Self>) {
+        self.sort = self.sort.next();
+        self.search(cx);
+        cx.notify();
+    }
+
+    // Appends results to the grid, the caller clears it first if those are for a new search.
+    fn show_results(&mut self, results: SearchResults, cx: &mut Context<Self>) {
+        if results.went_offline {
+            println!("NETWORK UNAVAILABLE, SHOWING OFFLINE RESULTS");
+        }
+        self.went_offline = results.went_offline;
+
+        self.error = results.errors.first().map(|err| err.to_string().into());
+        self.has_more = results.has_more;
+        for emote in results.emotes {
+            self.emotes.push(cx.new(|_cx| DisplayedEmote::new(emote)));
+        }
         cx.notify();
     }
 
@@ -393,63 +810,7 @@ impl EntityInputHandler for TextInput {
         self.marked_range.take();
         cx.notify();
 
-        // QUERY SECTION
-
-        let query = truncate_to_byte_limit(&self.content, 64).to_lowercase();
-        self.last_active.store(false, atomic::Ordering::Relaxed);
-        self.last_active = Arc::new(atomic::AtomicBool::new(true));
-
-        let last_active = self.last_active.clone();
-        cx.spawn(async move |entity, cx| {
-            cx.background_executor().timer(Duration::from_millis(200)).await;
-
-            if !last_active.load(atomic::Ordering::Relaxed) {
-                return;
-            }
-
-            println!("Potential query: {:?}", query);
-            let mut emotes: Vec<seventv::WebmEmote> = vec![];
-            if !query.is_empty() {
-                // emotes = seventv::get_7tv(query.to_sanitized_string()).await;
-                let queries_dir = format!("{}/queries", *CACHE_DIR);
-                fs::create_dir_all(queries_dir.clone()).expect("rip queries dir");
-                let query_fp = format!("{}/{}.json", queries_dir, sha256::digest(query.clone()));
-                if let Ok(mut file) = File::open(query_fp.clone()) {
-                    let mut contents = String::new();
-                    file.read_to_string(&mut contents).expect("rip file read");
-                    emotes = serde_json::from_str(&contents).expect("rip json load");
-                } else {
-                    println!("QUERYING: {:?}", query.clone());
-                    emotes = seventv::query_7tv(query.to_string()).await;
-                    let mut file = File::create(query_fp.clone()).expect("rip create file");
-                    file.write_all(serde_json::to_vec_pretty(&emotes).unwrap().as_ref())
-                        .expect("rip write file");
-                }
-
-                if !last_active.load(atomic::Ordering::Relaxed) {
-                    return;
-                }
-            }
-
-            entity
-                .update(cx, |new_self, cx| {
-                    if !last_active.load(atomic::Ordering::Relaxed) {
-                        return;
-                    }
-
-                    if query.is_empty() {
-                        emotes = new_self.recent_emotes.recent().cloned().collect();
-                    }
-
-                    new_self.emotes.clear();
-                    for emote in emotes {
-                        new_self.emotes.push(cx.new(|_cx| DisplayedEmote { emote }));
-                    }
-                    cx.notify();
-                })
-                .expect("rip updating text_input");
-        })
-        .detach();
+        self.search(cx);
     }
 
     fn replace_and_mark_text_in_range(
@@ -670,6 +1031,7 @@ impl Element for TextElement {
 
 impl Render for TextInput {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let colors = config::get().colors;
         div()
             .flex()
             .mt_6()
@@ -681,7 +1043,14 @@ impl Render for TextInput {
             .on_action(cx.listener(Self::backspace))
             .on_action(cx.listener(Self::show_recent_emotes))
             .on_action(cx.listener(Self::clear_input))
-            .bg(rgb(0x838ba7))
+            .on_action(cx.listener(Self::toggle_offline))
+            .on_action(cx.listener(Self::next_page))
+            .on_action(cx.listener(Self::next_sort))
+            .on_action(cx.listener(Self::cycle_animation_filter))
+            .on_action(cx.listener(Self::toggle_zero_width_filter))
+            .on_action(cx.listener(Self::toggle_copy_animated))
+            .on_action(cx.listener(Self::next_output_size))
+            .bg(colors.input.rgb())
             .line_height(px(30.))
             .text_size(px(24.))
             .w(px(320.))
@@ -690,7 +1059,7 @@ impl Render for TextInput {
                     .h(px(30. + 4. * 2.))
                     .w(px(320.))
                     .p(px(4.))
-                    .bg(rgb(0x838ba7))
+                    .bg(colors.input.rgb())
                     .overflow_x_hidden()
                     .border_color(gpui::blue())
                     .child(TextElement {
@@ -709,84 +1078,412 @@ impl Focusable for TextInput {
 struct InputExample {
     text_input: Entity<TextInput>,
     image_cache: Entity<cache::HashMapImageCache>,
+    scroll_handle: ScrollHandle,
+    grid_focus_handle: FocusHandle,
 }
 
 impl InputExample {
     fn exit(&mut self, _: &Escape, _window: &mut Window, _cx: &mut Context<Self>) {
         std::process::exit(0); // couldn't find any more proper way to close window
     }
+
+    // Tells the image cache what's in the grid. Images that are still downloading for emotes no
+    // longer in the grid (e.g. from a search that was typed over) are not worth waiting for.
+    fn update_shown_images(&mut self, cx: &mut Context<Self>) {
+        let shown: HashSet<String> = self
+            .text_input
+            .read(cx)
+            .emotes
+            .iter()
+            .map(|emote| emote.read(cx).emote.thumbnail_url().clone())
+            .chain([loading_image_url()])
+            .collect();
+        self.image_cache
+            .update(cx, |image_cache, _| image_cache.set_shown(shown));
+    }
+
+    // Images of the rows on screen, and of a screen worth of rows above and below, are never
+    // evicted. The rest of the grid keeps growing with infinite scroll, those images are loaded
+    // again from the disk cache when scrolled back to. Bounds are from the last layout, emotes
+    // that haven't been laid out yet are pinned on the next frame.
+    fn update_visible_images(&self, cx: &mut Context<Self>) {
+        let viewport = self.scroll_handle.bounds();
+        // bounds of the cells are laid out as if the grid wasn't scrolled
+        let offset = self.scroll_handle.offset().y;
+        let top = viewport.top() - offset - viewport.size.height;
+        let bottom = viewport.bottom() - offset + viewport.size.height;
+
+        let visible: HashSet<String> = self
+            .text_input
+            .read(cx)
+            .emotes
+            .iter()
+            .enumerate()
+            .filter(|(ix, _)| {
+                self.scroll_handle
+                    .bounds_for_item(*ix)
+                    .is_some_and(|bounds| bounds.bottom() >= top && bounds.top() <= bottom)
+            })
+            .map(|(_, emote)| emote.read(cx).emote.thumbnail_url().clone())
+            .chain([loading_image_url()])
+            .collect();
+        self.image_cache
+            .update(cx, |image_cache, _| image_cache.set_visible(visible));
+    }
+
+    fn on_sort_click(&mut self, _: &MouseUpEvent, _window: &mut Window, cx: &mut Context<Self>) {
+        self.text_input.update(cx, |text_input, cx| text_input.cycle_sort(cx));
+    }
+
+    fn left(&mut self, _: &Left, window: &mut Window, cx: &mut Context<Self>) {
+        self.move_selection(-1, window, cx);
+    }
+
+    fn right(&mut self, _: &Right, window: &mut Window, cx: &mut Context<Self>) {
+        self.move_selection(1, window, cx);
+    }
+
+    fn up(&mut self, _: &Up, window: &mut Window, cx: &mut Context<Self>) {
+        // going up from the first row gets back to typing
+        if self.text_input.read(cx).selected.is_none_or(|ix| ix < self.columns()) {
+            self.focus_search(&FocusSearch, window, cx);
+            return;
+        }
+        self.move_selection(-(self.columns() as isize), window, cx);
+    }
+
+    fn down(&mut self, _: &Down, window: &mut Window, cx: &mut Context<Self>) {
+        self.move_selection(self.columns() as isize, window, cx);
+    }
+
+    // Copies the selected emote, or the first result straight from the search input.
+    fn enter(&mut self, _: &Enter, _window: &mut Window, cx: &mut Context<Self>) {
+        self.text_input.update(cx, |text_input, cx| {
+            let ix = text_input.selected.unwrap_or(0);
+            text_input.copy_nth(ix, cx);
+        });
+    }
+
+    fn save_as(&mut self, _: &CtrlE, _window: &mut Window, cx: &mut Context<Self>) {
+        self.text_input.update(cx, |text_input, cx| {
+            let ix = text_input.selected.unwrap_or(0);
+            text_input.save_nth(ix, cx);
+        });
+    }
+
+    fn focus_search(&mut self, _: &FocusSearch, window: &mut Window, cx: &mut Context<Self>) {
+        self.text_input.update(cx, |text_input, cx| {
+            text_input.selected = None;
+            window.focus(&text_input.focus_handle(cx));
+            cx.notify();
+        });
+    }
+
+    // Number keys pick one of the first ten emotes, `0` being the tenth one.
+    fn on_grid_key_down(&mut self, event: &KeyDownEvent, _window: &mut Window, cx: &mut Context<Self>) {
+        let keystroke = &event.keystroke;
+        if keystroke.modifiers.modified() {
+            return;
+        }
+        let Some(digit) = keystroke.key.parse::<usize>().ok().filter(|d| *d <= 9) else {
+            return;
+        };
+
+        let ix = if digit == 0 { 9 } else { digit - 1 };
+        self.text_input.update(cx, |text_input, cx| text_input.copy_nth(ix, cx));
+        cx.stop_propagation();
+    }
+
+    fn move_selection(&mut self, delta: isize, window: &mut Window, cx: &mut Context<Self>) {
+        let columns = self.columns();
+        let selected = self.text_input.update(cx, |text_input, cx| {
+            let len = text_input.emotes.len();
+            if len == 0 {
+                return None;
+            }
+
+            let ix = match text_input.selected {
+                Some(ix) => (ix as isize + delta).clamp(0, len as isize - 1) as usize,
+                None => 0,
+            };
+            text_input.selected = Some(ix);
+
+            // reaching the last row works the same way as scrolling to the end of the grid
+            if ix + columns >= len {
+                text_input.load_more(cx);
+            }
+            cx.notify();
+            Some(ix)
+        });
+
+        if let Some(ix) = selected {
+            window.focus(&self.grid_focus_handle);
+            self.scroll_handle.scroll_to_item(ix);
+            cx.notify();
+        }
+    }
+
+    // The grid is flex-wrapped, so the amount of columns is whatever fits into the first row.
+    fn columns(&self) -> usize {
+        let Some(first) = self.scroll_handle.bounds_for_item(0) else {
+            return 1;
+        };
+        1 + (1..)
+            .take_while(|ix| {
+                self.scroll_handle
+                    .bounds_for_item(*ix)
+                    .is_some_and(|bounds| bounds.top() == first.top())
+            })
+            .count()
+    }
+
+    // Infinite scroll, next page is requested a bit before reaching the end of the grid.
+    fn on_scroll(&mut self, _: &ScrollWheelEvent, _window: &mut Window, cx: &mut Context<Self>) {
+        // offset goes into negative values when scrolling down
+        let remaining = self.scroll_handle.max_offset().height + self.scroll_handle.offset().y;
+        if remaining < px(200.) {
+            self.text_input.update(cx, |text_input, cx| text_input.load_more(cx));
+        }
+    }
+}
+
+impl InputExample {
+    // Wraps every emote into a grid cell with the focus ring, quick pick numbers are only shown
+    // while the grid itself is focused.
+    fn emote_cells(&self, window: &Window, cx: &App) -> Vec<Div> {
+        let colors = config::get().colors;
+        let text_input = self.text_input.read(cx);
+        let grid_focused = self.grid_focus_handle.is_focused(window);
+        text_input
+            .emotes
+            .iter()
+            .enumerate()
+            .map(|(ix, emote)| {
+                let selected = grid_focused && text_input.selected == Some(ix);
+                div()
+                    .relative()
+                    .ml_6()
+                    .mt_6()
+                    .border_2()
+                    .rounded_md()
+                    .border_color(if selected {
+                        colors.text.rgb().into()
+                    } else {
+                        gpui::transparent_black()
+                    })
+                    .child(emote.clone())
+                    .when(grid_focused && ix < 10, |cell| {
+                        cell.child(
+                            div()
+                                .absolute()
+                                .top_0()
+                                .left_0()
+                                .px_1()
+                                .bg(colors.bar.rgb())
+                                .text_color(colors.text.rgb())
+                                .text_size(px(10.))
+                                .child(format!("{}", (ix + 1) % 10)),
+                        )
+                    })
+            })
+            .collect()
+    }
 }
 
 impl Render for InputExample {
-    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        // the whole window is rendered again on every scroll
+        self.update_visible_images(cx);
+
+        let colors = config::get().colors;
         image_cache(self.image_cache.clone()).size_full().child(
             div()
-                .bg(rgb(0x626880))
+                .bg(colors.background.rgb())
                 .on_action(cx.listener(Self::exit))
+                .on_action(cx.listener(Self::left))
+                .on_action(cx.listener(Self::right))
+                .on_action(cx.listener(Self::up))
+                .on_action(cx.listener(Self::down))
+                .on_action(cx.listener(Self::enter))
+                .on_action(cx.listener(Self::save_as))
+                .on_action(cx.listener(Self::focus_search))
                 .flex()
                 .flex_col()
                 .size_full()
                 .child(
                     div()
-                        .bg(rgb(0x414559))
+                        .bg(colors.bar.rgb())
                         .border_b_1()
                         .border_color(black())
                         .flex()
                         .flex_row()
                         .justify_between()
+                        .child(
+                            div()
+                                .ml_2()
+                                .cursor_pointer()
+                                .text_color(colors.text.rgb())
+                                .on_mouse_up(MouseButton::Left, cx.listener(Self::on_sort_click))
+                                .child(format!("sort: {}", self.text_input.read(cx).sort.label())),
+                        )
+                        .when(!self.text_input.read(cx).filters.label().is_empty(), |header| {
+                            header.child(
+                                div()
+                                    .ml_2()
+                                    .text_color(colors.text.rgb())
+                                    .child(format!("filters: {}", self.text_input.read(cx).filters.label())),
+                            )
+                        })
                         .child(
                             div()
                                 .ml_auto()
                                 .mr_auto()
-                                .text_color(rgb(0xc6d0f5))
+                                .text_color(colors.text.rgb())
                                 .child(format!("{} - v{}", *APP_NAME, VERSION)),
+                        )
+                        .when(self.text_input.read(cx).output_size != OutputSize::Native, |header| {
+                            header.child(
+                                div()
+                                    .mr_2()
+                                    .text_color(colors.text.rgb())
+                                    .child(format!("size: {}", self.text_input.read(cx).output_size.label())),
+                            )
+                        })
+                        .when(self.text_input.read(cx).copy_animated, |header| {
+                            header.child(div().mr_2().text_color(colors.text.rgb()).child("animated copy"))
+                        })
+                        .when(
+                            self.text_input.read(cx).offline || self.text_input.read(cx).went_offline,
+                            |header| header.child(div().mr_2().text_color(colors.error.rgb()).child("offline")),
                         ),
                 )
                 .child(self.text_input.clone())
+                .when_some(self.text_input.read(cx).error.clone(), |root, error| {
+                    root.child(
+                        div()
+                            .mt_2()
+                            .ml_auto()
+                            .mr_auto()
+                            .px_2()
+                            .bg(colors.error.rgb())
+                            .text_color(colors.error_text.rgb())
+                            .child(error),
+                    )
+                })
                 .child(
                     div()
+                        .id("emotes")
+                        .key_context("EmoteGrid")
+                        .track_focus(&self.grid_focus_handle)
+                        .on_key_down(cx.listener(Self::on_grid_key_down))
+                        .flex_1()
+                        .overflow_y_scroll()
+                        .track_scroll(&self.scroll_handle)
+                        .on_scroll_wheel(cx.listener(Self::on_scroll))
                         .flex()
                         .flex_wrap()
-                        .mr_6()
-                        .mt_6()
-                        .children(self.text_input.read(cx).emotes.iter().map(|gif| gif.clone())),
+                        .content_start()
+                        .items_start()
+                        .pr_6()
+                        .pb_6()
+                        .children(self.emote_cells(window, cx)),
                 )
                 .child(
                     div()
-                        .bg(rgb(0x414559))
+                        .bg(colors.bar.rgb())
                         .border_b_1()
                         .mt_auto()
                         .border_color(black())
                         .child(
                             div()
                                 .ml_2()
-                                .text_color(rgb(0xc6d0f5))
+                                .text_color(colors.text.rgb())
                                 .flex()
                                 .flex_row()
                                 .gap_6()
-                                .child(format!("esc: Exit"))
-                                .child(format!("ctrl-space: Recent Emotes"))
-                                .child(format!("ctrl-s: Clear Search")),
+                                .child("esc: Exit")
+                                .child("ctrl-space: Recent Emotes")
+                                .child("ctrl-s: Clear Search")
+                                .child("ctrl-o: Offline Mode")
+                                .child("ctrl-n: More Results")
+                                .child("ctrl-t: Sort Order")
+                                .child("ctrl-a: Animated/Static")
+                                .child("ctrl-z: Zero-Width")
+                                .child("ctrl-g: Animated Copy")
+                                .child("ctrl-r: Copy Size")
+                                .child("shift-click: Copy Frame")
+                                .child("ctrl-e/right-click: Save As")
+                                .child("arrows/hjkl: Select")
+                                .child("enter/1-0: Copy"),
                         ),
                 ),
         )
     }
 }
 
+// Disk cache quota is enforced on startup and then every once in a while, since the picker can
+// be left running as a daemon.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 fn main() {
-    Application::new().run(|cx: &mut App| {
+    // before anything else, even the cache dir can be configured
+    let config_error = config::init();
+    xdg::migrate_legacy_dirs();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if disk_cache::run_command(&args) {
+        return;
+    }
+
+    Application::new().run(move |cx: &mut App| {
         cx.activate(true);
 
+        let executor = cx.background_executor().clone();
+        executor.spawn(async { provider::index_cached_queries() }).detach();
+        executor
+            .spawn({
+                let executor = executor.clone();
+                async move {
+                    loop {
+                        let report = disk_cache::prune(config::get().cache.disk_quota());
+                        if report.files > 0 {
+                            println!("PRUNED DISK CACHE: {} files, {} bytes", report.files, report.bytes)
+                        }
+                        executor.timer(PRUNE_INTERVAL).await;
+                    }
+                }
+            })
+            .detach();
+
         cx.bind_keys([
             KeyBinding::new("backspace", Backspace, None),
             KeyBinding::new("escape", Escape, None),
             KeyBinding::new("ctrl-space", CtrlSpace, None),
             KeyBinding::new("ctrl-s", CtrlS, None),
+            KeyBinding::new("ctrl-o", CtrlO, None),
+            KeyBinding::new("ctrl-n", CtrlN, None),
+            KeyBinding::new("ctrl-t", CtrlT, None),
+            KeyBinding::new("ctrl-a", CtrlA, None),
+            KeyBinding::new("ctrl-z", CtrlZ, None),
+            KeyBinding::new("ctrl-g", CtrlG, None),
+            KeyBinding::new("ctrl-e", CtrlE, None),
+            KeyBinding::new("ctrl-r", CtrlR, None),
+            KeyBinding::new("left", Left, None),
+            KeyBinding::new("right", Right, None),
+            KeyBinding::new("up", Up, None),
+            KeyBinding::new("down", Down, None),
+            KeyBinding::new("enter", Enter, None),
+            KeyBinding::new("h", Left, Some("EmoteGrid")),
+            KeyBinding::new("l", Right, Some("EmoteGrid")),
+            KeyBinding::new("k", Up, Some("EmoteGrid")),
+            KeyBinding::new("j", Down, Some("EmoteGrid")),
+            KeyBinding::new("/", FocusSearch, Some("EmoteGrid")),
+            KeyBinding::new("i", FocusSearch, Some("EmoteGrid")),
+            KeyBinding::new("backspace", FocusSearch, Some("EmoteGrid")),
         ]);
 
-        // width: 24 + (80 * 10 + 24 * 9) + 24 = 1064
-        // height: TODO
-        let bounds = Bounds::centered(None, size(px(1064.), px(850.)), cx);
+        let config = config::get();
+        let bounds = Bounds::centered(None, size(px(config.window.width), px(config.window.height)), cx);
         let window = cx
             .open_window(
                 WindowOptions {
@@ -794,9 +1491,9 @@ fn main() {
                     ..Default::default()
                 },
                 |_, cx| {
-                    let recent_emotes = RecentEmotes::new(15);
-                    cx.new(|cx| InputExample {
-                        text_input: cx.new(|cx| TextInput {
+                    let recent_emotes = RecentEmotes::new(config.recent_emotes);
+                    cx.new(|cx| {
+                        let text_input = cx.new(|cx| TextInput {
                             focus_handle: cx.focus_handle(),
                             content: "".into(),
                             placeholder: "Type here...".into(),
@@ -807,12 +1504,33 @@ fn main() {
                             last_bounds: None,
                             emotes: recent_emotes
                                 .recent()
-                                .map(|emote| cx.new(|_cx| DisplayedEmote { emote: emote.clone() }))
+                                .map(|emote| cx.new(|_cx| DisplayedEmote::new(emote.clone())))
                                 .collect(),
                             recent_emotes,
-                            last_active: Arc::new(atomic::AtomicBool::new(true)),
-                        }),
-                        image_cache: cache::HashMapImageCache::new(cx),
+                            providers: Providers::builtin(),
+                            offline: false,
+                            went_offline: false,
+                            error: config_error.as_ref().map(|err| err.to_string().into()),
+                            page: 1,
+                            has_more: false,
+                            loading_more: false,
+                            sort: Sort::default(),
+                            filters: Filters::default(),
+                            selected: None,
+                            copy_animated: false,
+                            output_size: config.output_size,
+                            search_task: None,
+                            load_more_task: None,
+                        });
+                        cx.observe(&text_input, |this, _, cx| this.update_shown_images(cx))
+                            .detach();
+
+                        InputExample {
+                            text_input,
+                            image_cache: cache::HashMapImageCache::new(cx),
+                            scroll_handle: ScrollHandle::new(),
+                            grid_focus_handle: cx.focus_handle(),
+                        }
                     })
                 },
             )
@@ -825,7 +1543,32 @@ fn main() {
                 window.set_app_id(&APP_NAME);
 
                 window.focus(&view.text_input.focus_handle(cx));
+                // the recent emotes are in the grid before anything notifies
+                view.update_shown_images(cx);
             })
             .unwrap();
+
+        // Changes of the config are picked up without restarting the picker.
+        cx.spawn(async move |cx| {
+            let mut modified = config::modified();
+            loop {
+                cx.background_executor().timer(config::RELOAD_INTERVAL).await;
+                if config::modified() == modified {
+                    continue;
+                }
+                modified = config::modified();
+
+                let result = config::reload();
+                let updated = window.update(cx, |view, window, cx| {
+                    view.text_input
+                        .update(cx, |text_input, cx| text_input.apply_config(result, cx));
+                    window.refresh();
+                });
+                if updated.is_err() {
+                    return; // window is gone
+                }
+            }
+        })
+        .detach();
     });
 }
@@ -0,0 +1,140 @@
+use crate::error::{Error, Result};
+use futures::AsyncReadExt as _;
+use futures::future::{BoxFuture, FutureExt, Shared, WeakShared};
+use gpui::http_client::{self, AsyncBody, HttpClient, StatusCode};
+use lazy_static::lazy_static;
+use reqwest_client::ReqwestClient;
+use smol::Timer;
+use smol::lock::Semaphore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+lazy_static! {
+    /// Single client for the whole app, so connections are reused between searches and downloads.
+    pub static ref CLIENT: Client = Client::new();
+}
+
+// Fast typing and scrolling through the grid shouldn't open dozens of connections at once.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+// Counted from the moment the request got its turn, not from when it was queued.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Fully read response, so the same one can be handed out to every deduplicated request.
+#[derive(Debug)]
+pub struct Response {
+    pub status: StatusCode,
+    pub body: Vec<u8>,
+}
+
+// Errors are strings here, shared futures need a cloneable output.
+type ResponseFuture = BoxFuture<'static, std::result::Result<Arc<Response>, String>>;
+
+pub struct Client {
+    client: Arc<ReqwestClient>,
+    // Only weak handles are kept, so a request is dropped (and cancelled) as soon as the last
+    // caller waiting for it is, e.g. when the search that wanted it has been superseded.
+    in_flight: Mutex<HashMap<String, WeakShared<ResponseFuture>>>,
+    permits: Arc<Semaphore>,
+}
+
+impl Client {
+    fn new() -> Self {
+        Self {
+            client: Arc::new(ReqwestClient::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS)),
+        }
+    }
+
+    pub async fn get(&self, url: &str) -> Result<Arc<Response>> {
+        let client = self.client.clone();
+        let owned_url = url.to_string();
+        self.send(url.to_string(), async move {
+            client.get(&owned_url, AsyncBody::empty(), true).await
+        })
+        .await
+    }
+
+    pub async fn post_json(&self, url: &str, body: Vec<u8>) -> Result<Arc<Response>> {
+        // the same query typed twice before the first one is back is the same request
+        let key = format!("{}#{}", url, sha256::digest(&body[..]));
+        let client = self.client.clone();
+        let owned_url = url.to_string();
+        self.send(key, async move {
+            client.post_json(&owned_url, AsyncBody::from_bytes(body.into())).await
+        })
+        .await
+    }
+
+    // Requests with the same key that are already in flight are joined instead of sent again.
+    async fn send(
+        &self,
+        key: String,
+        request: impl Future<Output = http_client::Result<http_client::Response<AsyncBody>>> + Send + 'static,
+    ) -> Result<Arc<Response>> {
+        let shared = {
+            let mut in_flight = self.in_flight.lock().expect("rip http lock");
+            // cancelled requests leave their entries behind
+            in_flight.retain(|_, request| request.upgrade().is_some());
+            match in_flight.get(&key).and_then(WeakShared::upgrade) {
+                Some(shared) => shared,
+                None => {
+                    let permits = self.permits.clone();
+                    let url = key.clone();
+                    let shared = async move {
+                        let _permit = permits.acquire_arc().await;
+                        let timeout = async {
+                            Timer::after(REQUEST_TIMEOUT).await;
+                            Err(format!("timed out after {}s", REQUEST_TIMEOUT.as_secs()))
+                        };
+                        smol::future::or(read_response(request), timeout)
+                            .await
+                            .map_err(|err| format!("{}: {}", url, err))
+                    }
+                    .boxed()
+                    .shared();
+                    in_flight.insert(key.clone(), shared.downgrade().expect("rip fresh request"));
+                    shared
+                }
+            }
+        };
+
+        let result = shared.await;
+        self.in_flight.lock().expect("rip http lock").remove(&key);
+        result.map_err(Error::Network)
+    }
+}
+
+async fn read_response(
+    request: impl Future<Output = http_client::Result<http_client::Response<AsyncBody>>>,
+) -> std::result::Result<Arc<Response>, String> {
+    let response = request.await.map_err(|err| err.to_string())?;
+    let status = response.status();
+
+    // the length is of the encoded body, it can be only compared to uncompressed responses
+    let expected_len = if response.headers().contains_key("content-encoding") {
+        None
+    } else {
+        response
+            .headers()
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok())
+    };
+
+    let mut body = Vec::new();
+    response
+        .into_body()
+        .read_to_end(&mut body)
+        .await
+        .map_err(|err| err.to_string())?;
+    if let Some(expected_len) = expected_len
+        && expected_len != body.len()
+    {
+        return Err(format!("incomplete response, {} of {} bytes", body.len(), expected_len));
+    }
+
+    Ok(Arc::new(Response { status, body }))
+}
@@ -0,0 +1,797 @@
+use crate::CACHE_DIR;
+use crate::config;
+use crate::disk_cache;
+use crate::error::{Error, Result};
+use crate::net;
+use futures::future::{BoxFuture, join_all};
+use gpui::ImageSource;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+lazy_static! {
+    // Every emote from the query cache by provider and id, see `index_cached_queries`.
+    static ref CACHED_EMOTES: RwLock<Option<HashMap<(String, String), Emote>>> = RwLock::new(None);
+}
+
+/// Emote model shared by every provider, this is what gets rendered in the grid, stored in the
+/// recent emotes and written into the query cache.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Emote {
+    // Older caches (and recent.json) were written before providers existed, those are all 7tv.
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub animated: bool,
+    #[serde(default)]
+    pub zero_width: bool,
+    // Every image the provider has for the emote, `url` is the best of those used for copying.
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+}
+
+/// Single image of the emote, providers serve the same emote in multiple scales and formats.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Variant {
+    pub url: String,
+    pub mime: String,
+    pub scale: u32,
+    // not every provider tells those upfront
+    pub width: Option<u32>,
+    pub frame_count: Option<u32>,
+}
+
+// Grid tiles are 80px, 2x of most providers is 56-64px high which is close enough.
+const THUMBNAIL_SCALE: u32 = 2;
+
+// Formats we can decode, in the order of preference for copying. Webp keeps the animation and
+// transparency at the smallest size, avif is not supported by `image` without dav1d.
+const PREFERRED_MIMES: [&str; 4] = ["image/webp", "image/gif", "image/png", "image/jpeg"];
+
+impl Variant {
+    /// Best variant for copying, the largest scale of the most preferred format we can decode.
+    pub fn best(variants: &[Variant]) -> Option<&Variant> {
+        PREFERRED_MIMES.iter().find_map(|mime| {
+            variants
+                .iter()
+                .filter(|variant| variant.mime == *mime)
+                .max_by_key(|variant| variant.scale)
+        })
+    }
+}
+
+fn default_provider() -> String {
+    crate::seventv::PROVIDER_ID.to_string()
+}
+
+impl Emote {
+    /// Same emote regardless of the name or the variants, those could be from an older cache.
+    pub fn same(&self, other: &Emote) -> bool {
+        self.provider == other.provider && self.id == other.id
+    }
+
+    /// Location of the emote image on disk, remote images are stored in the cache dir.
+    pub fn path(url: &str) -> String {
+        if Path::new(url).is_absolute() {
+            return url.to_string(); // local emote, nothing to download
+        }
+        format!("{}/webm/{}.{}", *CACHE_DIR, sha256::digest(url), extension(url))
+    }
+
+    /// Smaller image shown in the grid, the largest scale up to 2x in the same format as `url`.
+    /// The full size image is downloaded only when the emote is copied.
+    pub fn thumbnail_url(&self) -> &String {
+        let mime = self.variants.iter().find(|v| v.url == self.url).map(|v| &v.mime);
+        self.variants
+            .iter()
+            .filter(|v| Some(&v.mime) == mime && v.scale <= THUMBNAIL_SCALE)
+            .max_by_key(|v| v.scale)
+            .map(|v| &v.url)
+            .unwrap_or(&self.url)
+    }
+
+    /// Source for the `img` element, local files are passed as paths so that they don't go
+    /// through the download step of the image cache.
+    pub fn image_source(&self) -> ImageSource {
+        let url = self.thumbnail_url();
+        if Path::new(url).is_absolute() {
+            PathBuf::from(url).into()
+        } else {
+            url.clone().into()
+        }
+    }
+}
+
+// Extension of the image behind the url, this is only a hint for the file name and for other apps
+// reading it from the clipboard, decoding always guesses the format from the contents.
+fn extension(url: &str) -> &str {
+    let file_name = url.rsplit('/').next().unwrap_or_default();
+    match file_name.rsplit_once('.') {
+        Some((_, ext @ ("webp" | "png" | "gif" | "avif"))) => ext,
+        // ffz (and some other cdns) serve static emotes as png without an extension in the url
+        _ => "png",
+    }
+}
+
+/// Order of the search results. Not every provider supports all of them, those fall back to
+/// their closest (or default) order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Sort {
+    #[default]
+    TopAllTime,
+    TrendingDaily,
+    TrendingWeekly,
+    TrendingMonthly,
+    Newest,
+    Alphabetical,
+}
+
+impl Sort {
+    const ALL: [Sort; 6] = [
+        Sort::TopAllTime,
+        Sort::TrendingDaily,
+        Sort::TrendingWeekly,
+        Sort::TrendingMonthly,
+        Sort::Newest,
+        Sort::Alphabetical,
+    ];
+
+    /// Next sort order, wrapping around to the first one.
+    pub fn next(self) -> Self {
+        let pos = Self::ALL.iter().position(|sort| *sort == self).unwrap_or_default();
+        Self::ALL[(pos + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Sort::TopAllTime => "top all time",
+            Sort::TrendingDaily => "trending daily",
+            Sort::TrendingWeekly => "trending weekly",
+            Sort::TrendingMonthly => "trending monthly",
+            Sort::Newest => "newest",
+            Sort::Alphabetical => "alphabetical",
+        }
+    }
+}
+
+/// Which emotes to show depending on whether they are animated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Animation {
+    #[default]
+    Any,
+    AnimatedOnly,
+    StaticOnly,
+}
+
+impl Animation {
+    /// Next option, wrapping around to the first one.
+    pub fn next(self) -> Self {
+        match self {
+            Animation::Any => Animation::AnimatedOnly,
+            Animation::AnimatedOnly => Animation::StaticOnly,
+            Animation::StaticOnly => Animation::Any,
+        }
+    }
+}
+
+/// Filters are sent to the providers that support them, but also applied on all of the results,
+/// since most of the providers can't filter on their side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Filters {
+    pub animation: Animation,
+    pub exclude_zero_width: bool,
+}
+
+impl Filters {
+    pub fn matches(&self, emote: &Emote) -> bool {
+        let animation = match self.animation {
+            Animation::Any => true,
+            Animation::AnimatedOnly => emote.animated,
+            Animation::StaticOnly => !emote.animated,
+        };
+        animation && !(self.exclude_zero_width && emote.zero_width)
+    }
+
+    /// Short description of the active filters for the header, empty if there are none.
+    pub fn label(&self) -> String {
+        let mut labels = vec![];
+        match self.animation {
+            Animation::Any => {}
+            Animation::AnimatedOnly => labels.push("animated"),
+            Animation::StaticOnly => labels.push("static"),
+        }
+        if self.exclude_zero_width {
+            labels.push("no zero-width");
+        }
+        labels.join(", ")
+    }
+}
+
+/// Everything that describes a single search request. The whole query is a part of the cache
+/// key, so searches with different options don't collide.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct SearchQuery {
+    pub text: String,
+    /// Words starting with `#` in the input, e.g. `#cat`.
+    pub tags: Vec<String>,
+    /// Pages start from 1.
+    pub page: usize,
+    pub sort: Sort,
+    pub filters: Filters,
+}
+
+impl SearchQuery {
+    /// Splits the input into the text and tags.
+    pub fn parse(input: &str, page: usize, sort: Sort, filters: Filters) -> Self {
+        let (tags, words): (Vec<&str>, Vec<&str>) = input.split_whitespace().partition(|word| word.starts_with('#'));
+        Self {
+            text: words.join(" "),
+            tags: tags
+                .into_iter()
+                .map(|tag| tag.trim_start_matches('#').to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            page,
+            sort,
+            filters,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty() && self.tags.is_empty()
+    }
+
+    /// How long the cached results stay fresh, trending and newest emotes change much faster
+    /// than the all time top.
+    pub fn ttl(&self) -> Duration {
+        const HOUR: u64 = 60 * 60;
+        Duration::from_secs(match self.sort {
+            Sort::Newest => HOUR / 4,
+            Sort::TrendingDaily | Sort::TrendingWeekly | Sort::TrendingMonthly => HOUR,
+            Sort::TopAllTime | Sort::Alphabetical => 24 * HOUR,
+        })
+    }
+}
+
+/// How eagerly the input is searched while typing, the `[search]` section of the config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchSettings {
+    /// Milliseconds without typing before the providers are queried.
+    pub debounce_ms: u64,
+    /// Shorter queries are searched only offline, a single letter matches half of all emotes.
+    pub min_query_length: usize,
+    /// Emotes requested from each provider for a single page.
+    pub per_page: usize,
+}
+
+impl Default for SearchSettings {
+    fn default() -> Self {
+        Self {
+            debounce_ms: 200,
+            min_query_length: 2,
+            per_page: 50,
+        }
+    }
+}
+
+impl SearchSettings {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+
+    /// Whether the query is long enough to be sent to remote providers. Tags are always specific
+    /// enough, and can't be searched offline anyway.
+    pub fn is_remote(&self, query: &SearchQuery) -> bool {
+        !query.tags.is_empty() || query.text.chars().count() >= self.min_query_length
+    }
+}
+
+/// Single page of search results from one provider, this is also what's stored in the query cache.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Page {
+    pub emotes: Vec<Emote>,
+    pub has_more: bool,
+}
+
+/// Results of searching through all of the providers for one page.
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    pub emotes: Vec<Emote>,
+    /// Errors of the failed providers, those don't affect results of the others.
+    pub errors: Vec<Error>,
+    /// Whether any of the providers has more pages.
+    pub has_more: bool,
+    /// None of the remote providers could be reached, the results are from the offline search then.
+    pub went_offline: bool,
+    /// Some of the results are from cache older than the query ttl and should be revalidated.
+    pub stale: bool,
+}
+
+/// Source of emotes that can be searched from the picker.
+///
+/// Futures are boxed (same as gpui http client does it) so providers can be stored as trait
+/// objects and queried together.
+pub trait EmoteProvider: Send + Sync {
+    /// Short unique identifier, used to tag emotes and to key the query cache.
+    fn id(&self) -> &'static str;
+
+    /// Search emotes matching the query.
+    fn search(&self, query: SearchQuery) -> BoxFuture<'static, Result<Page>>;
+
+    /// Url of the image for the given emote id.
+    fn image_url(&self, id: &str) -> String;
+
+    /// Whether the provider can search by tags, others are skipped for queries with tags.
+    fn supports_tags(&self) -> bool {
+        false
+    }
+
+    /// Whether the query is worth sending to the provider at all. Skipped providers don't count
+    /// as reachable (or unreachable) and nothing is cached for them.
+    fn can_search(&self, query: &SearchQuery) -> bool {
+        query.tags.is_empty() || self.supports_tags()
+    }
+
+    /// Whether the provider needs network. Only remote providers are cached, and they are
+    /// replaced by the cached results in offline mode.
+    fn remote(&self) -> bool {
+        true
+    }
+
+    /// Configured settings the results depend on, those are a part of the query cache key so
+    /// changing them doesn't mix up pages cached before and after.
+    fn cache_key(&self) -> String {
+        format!("per_page={}", config::get().search.per_page)
+    }
+}
+
+/// All registered providers, searched together and merged into a single list of results.
+#[derive(Default, Clone)]
+pub struct Providers {
+    providers: Vec<Arc<dyn EmoteProvider>>,
+}
+
+impl Providers {
+    /// Providers that come with kemote.
+    pub fn builtin() -> Self {
+        let mut providers = Self::default();
+        if let Some(local) = crate::local::Local::from_config() {
+            providers = providers.register(local);
+        }
+
+        providers
+            .register(crate::seventv::SevenTv)
+            .register(crate::bttv::Bttv)
+            .register(crate::ffz::Ffz)
+    }
+
+    pub fn register(mut self, provider: impl EmoteProvider + 'static) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Search all providers at once, results are concatenated in the order of registration.
+    ///
+    /// Providers that can't be reached are just left out, only if none of the remote ones can be
+    /// reached it falls back to the offline search.
+    pub async fn search(&self, query: SearchQuery, offline: bool) -> SearchResults {
+        if offline {
+            return self.search_offline(query).await;
+        }
+
+        let providers: Vec<_> = self
+            .providers
+            .iter()
+            .filter(|provider| provider.can_search(&query))
+            .collect();
+        let remote = providers.iter().filter(|provider| provider.remote()).count();
+        let searches = providers
+            .into_iter()
+            .map(|provider| search_cached(provider.clone(), query.clone()));
+
+        let mut results = SearchResults::default();
+        for result in join_all(searches).await {
+            match result {
+                Ok(cached) => {
+                    results.emotes.extend(cached.page.emotes);
+                    results.has_more |= cached.page.has_more;
+                    results.stale |= cached.stale;
+                }
+                Err(err) => results.errors.push(err),
+            }
+        }
+
+        // a single flaky provider (or a timeout) is not a reason to throw away the others
+        if all_unreachable(remote, &results.errors) {
+            let errors = results.errors;
+            results = self.search_offline(query).await;
+            results.errors.extend(errors);
+            results.went_offline = true;
+            return results;
+        }
+
+        results.emotes.retain(|emote| query.filters.matches(emote));
+        results
+    }
+
+    /// Queries again the providers whose cached results of the query are stale. Returns the fresh
+    /// results only if anything was refreshed, failed providers just keep their stale cache.
+    pub async fn revalidate(&self, query: SearchQuery) -> Option<SearchResults> {
+        let refreshes = self
+            .providers
+            .iter()
+            .filter(|provider| provider.remote() && provider.can_search(&query))
+            .filter(|provider| query_cache_path(provider, &query).is_ok_and(|fp| is_stale(&fp, query.ttl())))
+            .map(|provider| refresh_cached(provider.clone(), query.clone()));
+
+        let mut refreshed = false;
+        for result in join_all(refreshes).await {
+            match result {
+                Ok(_) => refreshed = true,
+                Err(err) => println!("FAILED TO REVALIDATE QUERY: {}", err),
+            }
+        }
+
+        // everything refreshed is in the cache now, so this doesn't hit the network again
+        if refreshed {
+            Some(self.search(query, false).await)
+        } else {
+            None
+        }
+    }
+
+    /// Search only through what is available without network: non-remote providers and the
+    /// emotes from cached queries, which thumbnail (shown in the grid) or full size image (from
+    /// copying it) was already downloaded. Everything is returned as a single page, with at most
+    /// a page worth of the cached emotes.
+    async fn search_offline(&self, query: SearchQuery) -> SearchResults {
+        // cached emotes don't know their tags
+        if query.page > 1 || !query.tags.is_empty() {
+            return SearchResults::default();
+        }
+
+        let searches = self
+            .providers
+            .iter()
+            .filter(|provider| !provider.remote())
+            .map(|provider| provider.search(query.clone()));
+
+        let mut results = SearchResults::default();
+        for result in join_all(searches).await {
+            match result {
+                Ok(page) => results.emotes.extend(page.emotes),
+                Err(err) => results.errors.push(err),
+            }
+        }
+
+        // reading the queries and checking the images block, that's kept off the ui thread
+        let per_page = config::get().search.per_page;
+        let cached_query = query.clone();
+        let cached = smol::unblock(move || {
+            if CACHED_EMOTES.read().expect("rip cached emotes lock").is_none() {
+                index_cached_queries();
+            }
+            search_cached_emotes(&cached_query, per_page)
+        })
+        .await;
+        results.emotes.extend(cached);
+        results.emotes.retain(|emote| query.filters.matches(emote));
+        results
+    }
+}
+
+// Only providers that were actually asked count, a network error from each of them means that
+// we are offline rather than that a provider is down.
+fn all_unreachable(remote: usize, errors: &[Error]) -> bool {
+    remote > 0 && errors.iter().filter(|err| err.is_network()).count() == remote
+}
+
+struct CachedPage {
+    page: Page,
+    stale: bool,
+}
+
+// Results are stored per provider, so adding a new provider doesn't invalidate cache of others.
+// Stale results are still returned right away, those are revalidated separately.
+async fn search_cached(provider: Arc<dyn EmoteProvider>, query: SearchQuery) -> Result<CachedPage> {
+    if !provider.remote() {
+        let page = provider.search(query).await?;
+        return Ok(CachedPage { page, stale: false });
+    }
+
+    let query_fp = query_cache_path(&provider, &query)?;
+
+    // corrupted cache files are treated the same as missing ones and simply queried again
+    if let Ok(contents) = fs::read_to_string(&query_fp)
+        && let Ok(page) = serde_json::from_str(&contents)
+    {
+        disk_cache::touch(&query_fp);
+        let stale = is_stale(&query_fp, query.ttl());
+        return Ok(CachedPage { page, stale });
+    }
+
+    let page = refresh_cached(provider, query).await?;
+    Ok(CachedPage { page, stale: false })
+}
+
+async fn refresh_cached(provider: Arc<dyn EmoteProvider>, query: SearchQuery) -> Result<Page> {
+    let query_fp = query_cache_path(&provider, &query)?;
+
+    println!("QUERYING {}: {:?}", provider.id(), query.clone());
+    let page = provider.search(query).await?;
+    // Failing to write the cache is not a reason to throw away the results.
+    if let Err(err) = write_cached_query(&query_fp, &page) {
+        println!("FAILED TO CACHE QUERY: {}", err);
+    }
+
+    Ok(page)
+}
+
+// every page (and sort order, filters, etc) is cached separately
+fn query_cache_path(provider: &Arc<dyn EmoteProvider>, query: &SearchQuery) -> Result<String> {
+    let query_key = serde_json::to_string(query).map_err(io::Error::from)?;
+    Ok(format!(
+        "{}/queries/{}.json",
+        *CACHE_DIR,
+        sha256::digest(format!("{}:{}:{}", provider.id(), provider.cache_key(), query_key))
+    ))
+}
+
+// Modification time is when the query was cached, reading it only updates the access time.
+fn is_stale(query_fp: &str, ttl: Duration) -> bool {
+    fs::metadata(query_fp)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_none_or(|age| age > ttl)
+}
+
+fn write_cached_query(query_fp: &str, page: &Page) -> Result<()> {
+    disk_cache::write_atomic(query_fp, &serde_json::to_vec_pretty(page).map_err(io::Error::from)?)?;
+    add_to_index(&page.emotes);
+    Ok(())
+}
+
+/// Reads the whole response body, unsuccessful statuses are reported as api errors.
+pub fn read_body(provider: &'static str, response: &net::Response) -> Result<String> {
+    if !response.status.is_success() {
+        return Err(Error::Api(provider, format!("status {}", response.status)));
+    }
+    String::from_utf8(response.body.clone()).map_err(|e| Error::Api(provider, e.to_string()))
+}
+
+// Query files don't store the query itself, so instead we match on names of every cached emote.
+// The grid only downloads thumbnails, full size images are there only for the copied emotes.
+fn is_cached(emote: &Emote) -> bool {
+    Path::new(&Emote::path(emote.thumbnail_url())).exists() || Path::new(&Emote::path(&emote.url)).exists()
+}
+
+/// Reads every cached query into memory, so searching through the cache doesn't have to read all
+/// of it again on every key press. Queries cached after that are added as they come.
+pub fn index_cached_queries() {
+    let mut index: HashMap<(String, String), Emote> = HashMap::new();
+    if let Ok(entries) = fs::read_dir(format!("{}/queries", *CACHE_DIR)) {
+        for entry in entries.flatten() {
+            let Ok(contents) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            // queries cached before pagination are plain lists of emotes
+            let cached = match serde_json::from_str::<Page>(&contents) {
+                Ok(page) => page.emotes,
+                Err(_) => match serde_json::from_str::<Vec<Emote>>(&contents) {
+                    Ok(emotes) => emotes,
+                    Err(_) => continue,
+                },
+            };
+            // the same emote could be cached both with and without variants, so only the ids are compared
+            for emote in cached {
+                index.entry((emote.provider.clone(), emote.id.clone())).or_insert(emote);
+            }
+        }
+    }
+
+    println!("INDEXED CACHED EMOTES: {}", index.len());
+    *CACHED_EMOTES.write().expect("rip cached emotes lock") = Some(index);
+}
+
+// Freshly cached emotes replace the older ones, those could be from before variants existed.
+fn add_to_index(emotes: &[Emote]) {
+    if let Some(index) = CACHED_EMOTES.write().expect("rip cached emotes lock").as_mut() {
+        for emote in emotes {
+            index.insert((emote.provider.clone(), emote.id.clone()), emote.clone());
+        }
+    }
+}
+
+/// Cached emotes matching the query, which images were already downloaded. Nothing is found
+/// until the cache has been indexed.
+pub fn search_cached_emotes(query: &SearchQuery, limit: usize) -> Vec<Emote> {
+    let index = CACHED_EMOTES.read().expect("rip cached emotes lock");
+    let Some(index) = index.as_ref() else {
+        return vec![];
+    };
+
+    let mut emotes: Vec<&Emote> = index
+        .values()
+        .filter(|emote| emote.name.to_lowercase().contains(&query.text) && query.filters.matches(emote))
+        .collect();
+    emotes.sort_by(|a, b| a.name.cmp(&b.name));
+    // only as many files are checked as needed
+    emotes
+        .into_iter()
+        .filter(|emote| is_cached(emote))
+        .take(limit)
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emote(animated: bool, zero_width: bool) -> Emote {
+        Emote {
+            provider: "test".to_string(),
+            id: "id".to_string(),
+            name: "name".to_string(),
+            url: "https://example.com/emote.webp".to_string(),
+            animated,
+            zero_width,
+            variants: vec![],
+        }
+    }
+
+    fn variant(mime: &str, scale: u32) -> Variant {
+        Variant {
+            url: format!("https://example.com/{}x.{}", scale, mime.trim_start_matches("image/")),
+            mime: mime.to_string(),
+            scale,
+            width: None,
+            frame_count: None,
+        }
+    }
+
+    fn emote_with(variants: Vec<Variant>) -> Emote {
+        let url = Variant::best(&variants)
+            .map(|best| best.url.clone())
+            .unwrap_or_default();
+        Emote {
+            url,
+            variants,
+            ..emote(false, false)
+        }
+    }
+
+    fn query(input: &str) -> SearchQuery {
+        SearchQuery::parse(input, 1, Sort::default(), Filters::default())
+    }
+
+    #[test]
+    fn offline_only_if_every_provider_asked_is_unreachable() {
+        let network = || Error::Network("timeout".to_string());
+        let api = || Error::Api("test", "status 500".to_string());
+        assert!(all_unreachable(2, &[network(), network()]));
+        assert!(!all_unreachable(3, &[network(), network()]));
+        assert!(!all_unreachable(2, &[network(), api()]));
+        assert!(!all_unreachable(0, &[]));
+    }
+
+    #[test]
+    fn short_queries_skip_bttv() {
+        let bttv = crate::bttv::Bttv;
+        let seventv = crate::seventv::SevenTv;
+        assert!(!bttv.can_search(&query("ab")));
+        assert!(bttv.can_search(&query("abc")));
+        assert!(!bttv.can_search(&query("abc #cat")));
+        assert!(seventv.can_search(&query("ab")));
+        assert!(seventv.can_search(&query("ab #cat")));
+    }
+
+    #[test]
+    fn parse_text_and_tags() {
+        let query = SearchQuery::parse("  #cat happy #dog  face ", 2, Sort::Newest, Filters::default());
+        assert_eq!(query.text, "happy face");
+        assert_eq!(query.tags, vec!["cat", "dog"]);
+        assert_eq!(query.page, 2);
+        assert_eq!(query.sort, Sort::Newest);
+    }
+
+    #[test]
+    fn parse_only_hashes() {
+        let query = SearchQuery::parse("# ##", 1, Sort::default(), Filters::default());
+        assert!(query.tags.is_empty());
+        assert!(query.is_empty());
+
+        let query = SearchQuery::parse("##cat", 1, Sort::default(), Filters::default());
+        assert_eq!(query.tags, vec!["cat"]);
+    }
+
+    #[test]
+    fn filters_animation() {
+        let animated = Filters {
+            animation: Animation::AnimatedOnly,
+            ..Filters::default()
+        };
+        let still = Filters {
+            animation: Animation::StaticOnly,
+            ..Filters::default()
+        };
+        assert!(Filters::default().matches(&emote(true, false)));
+        assert!(Filters::default().matches(&emote(false, false)));
+        assert!(animated.matches(&emote(true, false)));
+        assert!(!animated.matches(&emote(false, false)));
+        assert!(still.matches(&emote(false, false)));
+        assert!(!still.matches(&emote(true, false)));
+    }
+
+    #[test]
+    fn filters_zero_width() {
+        let filters = Filters {
+            exclude_zero_width: true,
+            ..Filters::default()
+        };
+        assert!(Filters::default().matches(&emote(false, true)));
+        assert!(!filters.matches(&emote(false, true)));
+        assert!(filters.matches(&emote(false, false)));
+    }
+
+    #[test]
+    fn best_prefers_webp_then_the_largest_scale() {
+        let variants = vec![
+            variant("image/avif", 4),
+            variant("image/webp", 2),
+            variant("image/webp", 3),
+        ];
+        assert_eq!(Variant::best(&variants), Some(&variants[2]));
+
+        // no webp at all, a format we can decode still wins over the larger avif
+        let variants = vec![
+            variant("image/avif", 4),
+            variant("image/png", 1),
+            variant("image/gif", 2),
+        ];
+        assert_eq!(Variant::best(&variants), Some(&variants[2]));
+    }
+
+    #[test]
+    fn best_without_decodable_variants() {
+        assert_eq!(Variant::best(&[variant("image/avif", 4)]), None);
+        assert_eq!(Variant::best(&[]), None);
+    }
+
+    #[test]
+    fn thumbnail_is_up_to_2x_in_the_same_format() {
+        let emote = emote_with(vec![
+            variant("image/webp", 1),
+            variant("image/webp", 2),
+            variant("image/webp", 4),
+            variant("image/avif", 2),
+        ]);
+        assert_eq!(emote.url, "https://example.com/4x.webp");
+        assert_eq!(emote.thumbnail_url(), "https://example.com/2x.webp");
+    }
+
+    #[test]
+    fn thumbnail_without_2x() {
+        let emote = emote_with(vec![variant("image/webp", 1), variant("image/webp", 4)]);
+        assert_eq!(emote.thumbnail_url(), "https://example.com/1x.webp");
+
+        let emote = emote_with(vec![variant("image/webp", 3), variant("image/webp", 4)]);
+        assert_eq!(emote.thumbnail_url(), "https://example.com/4x.webp");
+    }
+
+    #[test]
+    fn thumbnail_of_emotes_cached_without_variants() {
+        let emote = emote(false, false);
+        assert_eq!(emote.thumbnail_url(), &emote.url);
+    }
+}
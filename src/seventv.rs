@@ -1,7 +1,8 @@
-use crate::CACHE_DIR;
-use futures::AsyncReadExt as _;
-use gpui::http_client::{AsyncBody, HttpClient};
-use reqwest_client::ReqwestClient;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::net;
+use crate::provider::{Animation, Emote, EmoteProvider, Page, SearchQuery, Sort, Variant, read_body};
+use futures::{FutureExt, future::BoxFuture};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
@@ -130,13 +131,21 @@ query EmoteSearch(
 }
 "#;
 
+#[derive(Serialize)]
+struct SearchFilters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    animated: Option<bool>,
+    #[serde(rename(serialize = "defaultZeroWidth"), skip_serializing_if = "Option::is_none")]
+    default_zero_width: Option<bool>,
+}
+
 #[derive(Serialize)]
 struct Variables {
-    query: String,
+    query: Option<String>,
     tags: Vec<String>,
     #[serde(rename(deserialize = "sortBy", serialize = "sortBy"))]
-    sort_by: String,
-    // filters: Option<Vec<String>>,
+    sort_by: &'static str,
+    filters: SearchFilters,
     page: usize,
     #[serde(rename(deserialize = "perPage", serialize = "perPage"))]
     per_page: usize,
@@ -161,17 +170,26 @@ pub struct Image {
     frame_count: usize,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Flags {
+    #[serde(rename(deserialize = "defaultZeroWidth", serialize = "defaultZeroWidth"))]
+    default_zero_width: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Item {
     id: String,
     #[serde(rename(deserialize = "defaultName", serialize = "defaultName"))]
     name: String,
+    flags: Flags,
     images: Vec<Image>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Items {
     items: Vec<Item>,
+    #[serde(rename(deserialize = "pageCount"))]
+    page_count: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -189,68 +207,110 @@ struct Data {
     data: Emotes,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct WebmEmote {
-    pub id: String,
-    pub name: String,
-    pub url: String,
+pub const PROVIDER_ID: &str = "7tv";
+
+pub struct SevenTv;
+
+impl EmoteProvider for SevenTv {
+    fn id(&self) -> &'static str {
+        PROVIDER_ID
+    }
+
+    fn search(&self, query: SearchQuery) -> BoxFuture<'static, Result<Page>> {
+        query_7tv(query).boxed()
+    }
+
+    fn image_url(&self, id: &str) -> String {
+        format!("https://cdn.7tv.app/emote/{}/4x.webp", id)
+    }
+
+    fn supports_tags(&self) -> bool {
+        true
+    }
+
+    fn cache_key(&self) -> String {
+        let config = config::get();
+        format!("per_page={}:api_url={}", config.search.per_page, config.seventv.api_url)
+    }
 }
 
-impl WebmEmote {
-    pub fn path(url: &String) -> String {
-        format!("{}/webm/{}.webp", *CACHE_DIR, sha256::digest(url))
+fn sort_by(sort: Sort) -> &'static str {
+    match sort {
+        Sort::TopAllTime => "TOP_ALL_TIME",
+        Sort::TrendingDaily => "TRENDING_DAILY",
+        Sort::TrendingWeekly => "TRENDING_WEEKLY",
+        Sort::TrendingMonthly => "TRENDING_MONTHLY",
+        Sort::Newest => "UPLOAD_DATE",
+        Sort::Alphabetical => "ALPHABETICAL",
     }
 }
 
-pub async fn query_7tv(query: String) -> Vec<WebmEmote> {
+pub async fn query_7tv(query: SearchQuery) -> Result<Page> {
+    let config = config::get();
     let payload = Payload {
         query: GQL_QUERY,
         // @NOTE: there are also optional filters and other options.
         variables: Variables {
-            query,
-            tags: vec![],
-            sort_by: "TOP_ALL_TIME".to_string(), // @TODO: should be an enum
-            page: 1,
-            per_page: 50,
+            // tag only searches shouldn't match on the name at all
+            query: (!query.text.is_empty()).then_some(query.text),
+            tags: query.tags,
+            sort_by: sort_by(query.sort),
+            filters: SearchFilters {
+                animated: match query.filters.animation {
+                    Animation::Any => None,
+                    Animation::AnimatedOnly => Some(true),
+                    Animation::StaticOnly => Some(false),
+                },
+                default_zero_width: query.filters.exclude_zero_width.then_some(false),
+            },
+            page: query.page,
+            per_page: config.search.per_page,
         },
     };
 
-    // @TODO: Client can be created once for the whole gpui cx and passed in here?
-    let client = ReqwestClient::new();
-    let raw_payload = AsyncBody::from_bytes(serde_json::to_vec(&payload).expect("rip payload serialization").into());
-    let mut raw_response = String::new(); // maybe replace with read to bytes
-    client
-        .post_json("https://api.7tv.app/v4/gql", raw_payload)
-        .await
-        .expect("rip gpui reqwest post json")
-        .into_body()
-        .read_to_string(&mut raw_response)
-        .await
-        .unwrap();
+    let raw_payload = serde_json::to_vec(&payload).expect("rip payload serialization");
+    let response = net::CLIENT.post_json(&config.seventv.api_url, raw_payload).await?;
+    let raw_response = read_body(PROVIDER_ID, &response)?;
 
     // unpacking nested response schema
-    let items = serde_json::from_str::<Data>(&raw_response)
-        .expect("rip json response load")
+    let search = serde_json::from_str::<Data>(&raw_response)
+        .map_err(|e| Error::Api(PROVIDER_ID, e.to_string()))?
         .data
         .emotes
-        .search
-        .items;
+        .search;
 
-    items
+    let emotes = search
+        .items
         .into_iter()
-        .map(|emote| {
-            Some(WebmEmote {
-                id: emote.id.clone(),
-                name: emote.name.clone(),
-                url: emote
-                    .images
-                    .iter()
-                    .find(|o| o.scale == 4 && o.mime == "image/webp")
-                    .expect("rip finding specified image mime")
-                    .url
-                    .clone(),
+        .filter_map(|emote| {
+            let variants: Vec<Variant> = emote
+                .images
+                .into_iter()
+                .map(|image| Variant {
+                    url: image.url,
+                    mime: image.mime,
+                    scale: image.scale as u32,
+                    width: Some(image.width as u32),
+                    frame_count: Some(image.frame_count as u32),
+                })
+                .collect();
+
+            // skipping emotes that don't have any image we could decode (or are still processing)
+            let url = Variant::best(&variants)?.url.clone();
+            Some(Emote {
+                provider: PROVIDER_ID.to_string(),
+                id: emote.id,
+                name: emote.name,
+                url,
+                animated: variants.iter().any(|v| v.frame_count.is_some_and(|count| count > 1)),
+                zero_width: emote.flags.default_zero_width,
+                variants,
             })
         })
-        .filter_map(|e| e)
-        .collect()
+        .collect();
+
+    Ok(Page {
+        emotes,
+        has_more: query.page < search.page_count,
+    })
 }
@@ -0,0 +1,111 @@
+use crate::CACHE_DIR;
+use crate::clipboard::OutputSize;
+use crate::disk_cache;
+use crate::error::Result;
+use crate::provider::Emote;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, Delay, Frame, ImageReader, Rgba};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+// Browsers (and most chat clients) play anything faster than 20ms at 100ms instead.
+const MIN_DELAY_MS: u32 = 20;
+
+// Gif only has on/off transparency, half transparent edges are either kept or dropped.
+const ALPHA_THRESHOLD: u8 = 128;
+
+// 1 is the best palette quantization and 30 the fastest one, 10 is the encoder default.
+const QUANTIZATION_SPEED: i32 = 10;
+
+/// Location of the gif transcode, stored in the disk cache right next to the original webp.
+/// Resized transcodes get the size in the name.
+pub fn gif_path(url: &str, size: OutputSize) -> String {
+    let suffix = match size {
+        OutputSize::Native => String::new(),
+        _ => format!(".{}", size.label()),
+    };
+    format!("{}/webm/{}{}.gif", *CACHE_DIR, sha256::digest(url), suffix)
+}
+
+/// Transcode the cached webp of the emote into a gif, keeping frame delays and transparency.
+/// The transcode is done only once, after that the cached gif is returned right away.
+pub fn webp_to_gif(emote: &Emote, size: OutputSize) -> Result<String> {
+    let gif_path = gif_path(&emote.url, size);
+    if fs::read(&gif_path).is_ok_and(|bytes| disk_cache::is_complete_image(&bytes)) {
+        disk_cache::touch(&gif_path);
+        return Ok(gif_path);
+    }
+
+    let file = File::open(Emote::path(&emote.url))?;
+    let mut webp_decoder = WebPDecoder::new(BufReader::new(file))?;
+    webp_decoder.set_background_color(Rgba([0, 0, 0, 0]))?;
+
+    let mut gif: Vec<u8> = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut gif, QUANTIZATION_SPEED);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.try_encode_frames(
+            webp_decoder
+                .into_frames()
+                .map(|frame| frame.map(|frame| prepare_frame(frame, size))),
+        )?;
+    }
+
+    disk_cache::write_atomic(&gif_path, &gif)?;
+    Ok(gif_path)
+}
+
+/// Save the emote into `path`, converting it into the format of the path extension. Animated
+/// webp emotes saved as `.gif` get the gif transcode, paths without an extension keep the
+/// original format.
+pub fn export(emote: &Emote, path: &Path) -> Result<PathBuf> {
+    let source_path = Emote::path(&emote.url);
+    let source_ext = extension(Path::new(&source_path));
+
+    let mut path = path.to_path_buf();
+    if path.extension().is_none()
+        && let Some(ext) = &source_ext
+    {
+        path.set_extension(ext);
+    }
+
+    match (source_ext.as_deref(), extension(&path).as_deref()) {
+        (source, target) if source == target => {
+            fs::copy(&source_path, &path)?;
+        }
+        (Some("webp"), Some("gif")) => {
+            fs::copy(webp_to_gif(emote, OutputSize::Native)?, &path)?;
+        }
+        // everything else is converted as a static image
+        _ => ImageReader::open(&source_path)?
+            .with_guessed_format()?
+            .decode()?
+            .save(&path)?,
+    }
+
+    Ok(path)
+}
+
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+}
+
+fn prepare_frame(frame: Frame, size: OutputSize) -> Frame {
+    let (left, top) = (frame.left(), frame.top());
+    let (numer, denom) = frame.delay().numer_denom_ms();
+    let delay = if numer < MIN_DELAY_MS * denom {
+        Delay::from_numer_denom_ms(MIN_DELAY_MS, 1)
+    } else {
+        frame.delay()
+    };
+
+    let mut buffer = size.resize(frame.into_buffer());
+    for pixel in buffer.pixels_mut() {
+        pixel[3] = if pixel[3] < ALPHA_THRESHOLD { 0 } else { 255 };
+    }
+    Frame::from_parts(buffer, left, top, delay)
+}
@@ -0,0 +1,89 @@
+use crate::{APP_NAME, CACHE_DIR, STATE_DIR};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Downloaded images and cached queries, everything there can be thrown away.
+pub fn cache_home() -> PathBuf {
+    base_dir("XDG_CACHE_HOME", ".cache")
+}
+
+pub fn config_home() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Recent emotes, those are not worth a backup but losing them with the cache would be annoying.
+pub fn state_home() -> PathBuf {
+    base_dir("XDG_STATE_HOME", ".local/state")
+}
+
+fn home() -> Option<PathBuf> {
+    env::var_os("HOME").map(PathBuf::from).filter(|home| home.is_absolute())
+}
+
+// Relative paths are invalid according to the spec and ignored. Without a home directory at all
+// (e.g. under some service managers) nothing survives a reboot, but the picker still works.
+fn base_dir(var: &str, fallback: &str) -> PathBuf {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home().map(|home| home.join(fallback)))
+        .unwrap_or_else(env::temp_dir)
+}
+
+/// One-time move from the old layout, where everything (recent emotes included) was in
+/// `~/.cache/kemote` regardless of `XDG_CACHE_HOME`. Anything already in the new place is kept.
+pub fn migrate_legacy_dirs() {
+    let Some(home) = home() else {
+        return;
+    };
+    let legacy_dir = home.join(".cache").join(&*APP_NAME);
+    if !legacy_dir.exists() {
+        return;
+    }
+
+    let moves = [
+        (
+            legacy_dir.join("recent.json"),
+            Path::new(&*STATE_DIR).join("recent.json"),
+        ),
+        (legacy_dir.join("webm"), Path::new(&*CACHE_DIR).join("webm")),
+        (legacy_dir.join("queries"), Path::new(&*CACHE_DIR).join("queries")),
+    ];
+    for (from, to) in moves {
+        if from == to || !from.exists() || to.exists() {
+            continue;
+        }
+        match move_path(&from, &to) {
+            Ok(()) => println!("MIGRATED {:?} TO {:?}", from, to),
+            Err(err) => println!("FAILED TO MIGRATE {:?}: {}", from, err),
+        }
+    }
+
+    // only succeeds once everything has been moved out
+    _ = fs::remove_dir(&legacy_dir);
+}
+
+// Renames don't work across filesystems, and the cache on tmpfs is not that unusual. Cache dirs
+// are flat, so the copy doesn't need to recurse.
+fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+        fs::remove_dir_all(from)
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)
+    }
+}